@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::environment::Environment;
+use crate::evaluator::{Interpreter, RunTimeError, Unwind, Value};
+use crate::class::LoxInstance;
+use crate::parser::FunctionDecl;
+use crate::scanner::Token;
+
+pub trait Callable {
+    fn arity(&self) -> usize;
+
+    // `paren` is the closing paren of the call, used to locate errors raised by the callee.
+    // Takes an Rc so a class can hand itself to the instances it creates.
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RunTimeError>;
+}
+
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    // a method looked up on an instance gets its own scope with `this` bound to that instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        LoxFunction {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    fn bound_this(&self) -> Value {
+        self.closure.borrow().lookup("this").unwrap_or(Value::Nil)
+    }
+}
+
+// the closure can reach back to the function itself, so don't let Debug walk into it
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        _paren: &Token,
+    ) -> Result<Value, RunTimeError> {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment))) {
+            // an initializer always hands back the instance, even on an early `return;`
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.bound_this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+            // the parser never lets these escape a function body
+            Err(Unwind::Break | Unwind::Continue) => Ok(Value::Nil),
+        }
+    }
+}
+
+pub type NativeFn = fn(&mut Interpreter, &[Value], &Token) -> Result<Value, RunTimeError>;
+
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl Callable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RunTimeError> {
+        (self.function)(interpreter, &arguments, paren)
+    }
+}
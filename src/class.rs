@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::callable::{Callable, LoxFunction};
+use crate::evaluator::{Interpreter, RunTimeError, Value};
+use crate::scanner::Token;
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    // methods declared on the class win over anything inherited
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn is_subclass_of(self: &Rc<Self>, class: &Rc<LoxClass>) -> bool {
+        Rc::ptr_eq(self, class)
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|superclass| superclass.is_subclass_of(class))
+    }
+}
+
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl Callable for LoxClass {
+    fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    // calling a class constructs an instance and runs `init` on it, if there is one
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RunTimeError> {
+        let initializer = self.find_method("init");
+        let mut instance = LoxInstance::new(Rc::clone(&self));
+        if interpreter.is_error_class(&self) {
+            instance.set("line", Value::Int(paren.line as i64));
+        }
+        let instance = Rc::new(RefCell::new(instance));
+
+        if let Some(initializer) = initializer {
+            Rc::new(initializer.bind(Rc::clone(&instance))).call(interpreter, arguments, paren)?;
+        }
+
+        Ok(Value::Instance(instance))
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // takes the instance by Rc so a method found on the class can be bound to it
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RunTimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(RunTimeError::new(
+                name,
+                &format!("Undefined property '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.fields.insert(name.to_string(), value);
+    }
+
+    // a plain field lookup, for the interpreter's own use where methods don't matter
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
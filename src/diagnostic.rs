@@ -0,0 +1,212 @@
+use std::io::{self, IsTerminal};
+use crate::scanner::Span;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A stretch of source to underline, with an optional message printed next to it
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: Option<String>,
+}
+
+/*
+Everything needed to explain a problem in the source: the usual one-line summary, the span it is
+about, other spans worth pointing at ("opening paren was here") and suggestions printed at the
+end. The summary keeps the `[line N] Error ...` form, so anything matching on that still works.
+ */
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Label,
+    pub secondary: Vec<Label>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(message: String, span: Span) -> Self {
+        Self::new(Severity::Warning, message, span)
+    }
+
+    fn new(severity: Severity, message: String, span: Span) -> Self {
+        Self {
+            severity,
+            message,
+            primary: Label {
+                span,
+                message: None,
+            },
+            secondary: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: Some(message.to_string()),
+        });
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help.push(help);
+        self
+    }
+}
+
+/*
+Prints diagnostics against the file they came from:
+
+    [line 2] Error at ';': Expect ')' after expression.
+      --> script.lox:2:5
+       |
+     2 |   (2;
+       |     ^
+       |   - opening paren was here
+
+Colour is only used when stderr is a terminal, so redirected output stays plain text.
+ */
+pub struct Renderer<'a> {
+    file_name: &'a str,
+    source: &'a str,
+    lines: Vec<&'a str>,
+    colour: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file_name: &'a str, source: &'a str) -> Self {
+        Self {
+            file_name,
+            source,
+            lines: source.lines().collect(),
+            colour: io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn emit(&self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            eprint!("{}", self.render(diagnostic));
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let accent = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let mut out = format!("{}\n", self.paint(&diagnostic.message, accent));
+
+        let start = diagnostic.primary.span.start;
+        // synthesised tokens have no place in the source to show
+        if start.line == 0 {
+            return out;
+        }
+
+        let mut labels: Vec<(&Label, bool)> = vec![(&diagnostic.primary, true)];
+        labels.extend(diagnostic.secondary.iter().map(|label| (label, false)));
+        let mut lines: Vec<usize> = labels.iter().map(|(label, _)| label.span.start.line).collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let width = lines.last().map_or(1, |line| line.to_string().len());
+        let gutter = self.paint(&format!("{} |", " ".repeat(width)), BLUE);
+
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            " ".repeat(width),
+            self.paint("-->", BLUE),
+            self.file_name,
+            start.line,
+            start.column
+        ));
+        out.push_str(&format!("{}\n", gutter));
+
+        let mut previous: Option<usize> = None;
+        for line in lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                out.push_str(&format!("{}\n", self.paint("...", BLUE)));
+            }
+            previous = Some(line);
+
+            let text = self.lines.get(line - 1).copied().unwrap_or("");
+            out.push_str(&format!(
+                "{} {}\n",
+                self.paint(&format!("{:>width$} |", line), BLUE),
+                text
+            ));
+
+            let mut on_line: Vec<&(&Label, bool)> = labels
+                .iter()
+                .filter(|(label, _)| label.span.start.line == line)
+                .collect();
+            on_line.sort_by_key(|(label, primary)| (!primary, label.span.start.column));
+            for (label, primary) in on_line {
+                let (mark, colour) = if *primary { ('^', accent) } else { ('-', BLUE) };
+                let mut underline = mark.to_string().repeat(self.underline_width(label.span));
+                if let Some(message) = &label.message {
+                    underline.push(' ');
+                    underline.push_str(message);
+                }
+                out.push_str(&format!(
+                    "{} {}{}\n",
+                    gutter,
+                    indentation(text, label.span.start.column),
+                    self.paint(&underline, colour)
+                ));
+            }
+        }
+
+        for help in &diagnostic.help {
+            out.push_str(&format!(
+                "{} {} {}\n",
+                " ".repeat(width),
+                self.paint("= help:", BOLD),
+                help
+            ));
+        }
+        out
+    }
+
+    // how many characters of the span's first line to underline, at least one so that empty
+    // spans like the end of the file still get a caret
+    fn underline_width(&self, span: Span) -> usize {
+        self.source
+            .get(span.start.offset..span.end.offset)
+            .map_or(0, |text| text.chars().take_while(|c| *c != '\n').count())
+            .max(1)
+    }
+
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.colour {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// lines the underline up with the given column, keeping tabs so it stays aligned however wide
+// the terminal draws them
+fn indentation(text: &str, column: usize) -> String {
+    text.chars()
+        .chain(std::iter::repeat(' '))
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::callable::Callable;
+use crate::evaluator::{Interpreter, RunTimeError, Value};
+use crate::scanner::Token;
+
+pub struct LoxEnum {
+    pub name: String,
+    pub variants: Vec<Rc<LoxVariant>>,
+}
+
+impl LoxEnum {
+    pub fn find_variant(&self, name: &str) -> Option<Rc<LoxVariant>> {
+        self.variants
+            .iter()
+            .find(|variant| variant.name == name)
+            .map(Rc::clone)
+    }
+
+    // a variant without fields is already a value, one with fields is its constructor
+    pub fn get(&self, name: &Token) -> Result<Value, RunTimeError> {
+        match self.find_variant(&name.lexeme) {
+            Some(variant) if variant.fields.is_empty() => Ok(Value::Tagged(Rc::new(EnumValue {
+                variant,
+                values: Vec::new(),
+            }))),
+            Some(variant) => Ok(Value::Variant(variant)),
+            None => Err(RunTimeError::new(
+                name,
+                &format!("Undefined variant '{}' of enum {}.", name.lexeme, self.name),
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for LoxEnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<enum {}>", self.name)
+    }
+}
+
+pub struct LoxVariant {
+    pub enum_name: String,
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl fmt::Debug for LoxVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<variant {}.{}>", self.enum_name, self.name)
+    }
+}
+
+impl Callable for LoxVariant {
+    fn arity(&self) -> usize {
+        self.fields.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        _paren: &Token,
+    ) -> Result<Value, RunTimeError> {
+        Ok(Value::Tagged(Rc::new(EnumValue {
+            variant: self,
+            values: arguments,
+        })))
+    }
+}
+
+// Immutable once built; the values line up with the variant's field names
+pub struct EnumValue {
+    pub variant: Rc<LoxVariant>,
+    pub values: Vec<Value>,
+}
+
+impl EnumValue {
+    pub fn get(&self, name: &Token) -> Result<Value, RunTimeError> {
+        self.variant
+            .fields
+            .iter()
+            .position(|field| *field == name.lexeme)
+            .map(|position| self.values[position].clone())
+            .ok_or_else(|| {
+                RunTimeError::new(name, &format!("Undefined property '{}'.", name.lexeme))
+            })
+    }
+}
+
+impl fmt::Debug for EnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}.{} value>", self.variant.enum_name, self.variant.name)
+    }
+}
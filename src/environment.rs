@@ -0,0 +1,106 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::evaluator::{RunTimeError, Value};
+use crate::scanner::Token;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        // redefining an existing name in the same scope is allowed, the new value just wins
+        self.values.insert(name.to_string(), value);
+    }
+
+    // looks only at this scope, without walking outward or raising an error
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RunTimeError> {
+        match self.values.get(&name.lexeme) {
+            Some(value) => Ok(value.clone()),
+            None if self.enclosing.is_some() => {
+                self.enclosing.as_ref().unwrap().borrow().get(name)
+            }
+            None => Err(RunTimeError::new(
+                name,
+                &format!("Undefined variable '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RunTimeError> {
+        match self.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None if self.enclosing.is_some() => {
+                self.enclosing.as_ref().unwrap().borrow_mut().assign(name, value)
+            }
+            None => Err(RunTimeError::new(
+                name,
+                &format!("Undefined variable '{}'.", name.lexeme),
+            )),
+        }
+    }
+
+    // names the resolver left unresolved are globals, and those live at the root of the chain;
+    // every module has a root of its own, so this can't just go through one shared scope
+    pub fn get_global(&self, name: &Token) -> Result<Value, RunTimeError> {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_global(name),
+            None => self.get(name),
+        }
+    }
+
+    pub fn assign_global(&mut self, name: &Token, value: Value) -> Result<(), RunTimeError> {
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_global(name, value),
+            None => self.assign(name, value),
+        }
+    }
+
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RunTimeError> {
+        if distance == 0 {
+            return self.get(name);
+        }
+        self.ancestor(distance).borrow().get(name)
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RunTimeError> {
+        if distance == 0 {
+            return self.assign(name, value);
+        }
+        self.ancestor(distance).borrow_mut().assign(name, value)
+    }
+
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
+        // the resolver guarantees the chain is at least `distance` scopes deep
+        let mut environment = Rc::clone(self.enclosing.as_ref().unwrap());
+        for _ in 1..distance {
+            let enclosing = Rc::clone(environment.borrow().enclosing.as_ref().unwrap());
+            environment = enclosing;
+        }
+        environment
+    }
+}
@@ -1,48 +1,224 @@
-use std::f32::consts::E; // Kept for illustration; this might not be needed depending on context.
-use crate::parser::{Expr, Object, ParseError}; // Assumed modules
-use crate::scanner::{Literal, Token, TokenType};
-use crate::scanner::TokenType::Plus; // Correctly used below
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::callable::{Callable, LoxFunction, NativeFunction};
+use crate::natives;
+use crate::diagnostic::{Diagnostic, Renderer};
+use crate::class::{LoxClass, LoxInstance};
+use crate::enums::{EnumValue, LoxEnum, LoxVariant};
+use crate::environment::Environment;
+use crate::map::{LoxMap, MapKey};
+use crate::module::LoxModule;
+use crate::parser::{self, Expr, MatchArm, Parser, Pattern, Stmt};
+use crate::resolver::Resolver;
+use crate::scanner::{Literal, Scanner, Span, Token, TokenType};
 
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Nil,
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    // lists are shared by reference, so a mutation is visible through every alias
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    Module(Rc<LoxModule>),
+    Enum(Rc<LoxEnum>),
+    // the constructor of a variant that has fields
+    Variant(Rc<LoxVariant>),
+    // a value built from one of an enum's variants
+    Tagged(Rc<EnumValue>),
+}
 
+impl From<Literal> for Value {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::Int(n) => Value::Int(n),
+            Literal::Number(n) => Value::Number(n),
+            Literal::String(s) => Value::String(s),
+            Literal::Bool(b) => Value::Bool(b),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct RunTimeError {
-    pub token: Token,
+    // boxed now that tokens carry their spans, to keep every Result the evaluator returns small
+    pub token: Box<Token>,
     pub message: String,
+    // set when the error was raised by a `throw`, so `catch` hands back the original value
+    pub thrown: Option<Value>,
 }
 
 impl RunTimeError {
     pub fn new(token: &Token, message: &str) -> RunTimeError {
         RunTimeError {
-            token: token.clone(),
+            token: Box::new(token.clone()),
             message: message.to_string(),
+            thrown: None,
         }
     }
 
 }
 
-pub struct Interpreter {} // Fixed typo in struct name
+impl RunTimeError {
+    // points the error at a whole expression rather than the one token that raised it; the
+    // reported line stays that token's
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.token.span = span;
+        self
+    }
+
+    // keeps the `message` / `[line N]` header and adds the source the token points at
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.to_string(), self.token.span)
+    }
+}
+
+impl std::fmt::Display for RunTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.token.line)
+    }
+}
+
+impl std::error::Error for RunTimeError {
+}
+
+// Anything that stops a statement from completing normally; a `return` unwinds the same way
+// an error does until the enclosing function call catches it, `break` and `continue` until the
+// enclosing loop does
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RunTimeError),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+impl From<RunTimeError> for Unwind {
+    fn from(error: RunTimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    environment: Rc<RefCell<Environment>>,
+    // expression id -> number of scopes between the use and its binding, filled in by the Resolver
+    locals: HashMap<usize, usize>,
+    // the global `Error` class, errors raised by the interpreter itself are caught as instances of it
+    error_class: Rc<LoxClass>,
+    // every module that finished loading, by canonical path, so each one only ever runs once
+    modules: HashMap<PathBuf, Rc<LoxModule>>,
+    // the files currently being executed, innermost last, to catch cyclic imports
+    loading: Vec<PathBuf>,
+    // the source of every file run so far, keyed the way its tokens name it, so a runtime
+    // error can be shown against the file it actually happened in
+    sources: HashMap<PathBuf, String>,
+    // how many calls are in progress, runaway recursion is stopped at MAX_CALL_DEPTH
+    call_depth: usize,
+}
+
+const MAX_CALL_DEPTH: usize = 1000;
+
+// Built-ins that are easier to write in Lox itself. `Error` being an ordinary class means user
+// classes can extend it and call `super.init(message)`; `line` is filled in on construction.
+const PRELUDE: &str = "class Error { init(message) { this.message = message; } }";
 
 impl Interpreter {
     pub(crate) fn new() -> Interpreter {
-        Interpreter {}
+        let mut globals = Environment::new();
+        natives::define_natives(&mut globals);
+
+        let mut interpreter = Interpreter {
+            environment: Rc::new(RefCell::new(globals)),
+            locals: HashMap::new(),
+            // replaced by the real one as soon as the prelude has run
+            error_class: Rc::new(LoxClass {
+                name: "Error".to_string(),
+                superclass: None,
+                methods: HashMap::new(),
+            }),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            sources: HashMap::new(),
+            call_depth: 0,
+        };
+        interpreter.error_class = interpreter.run_prelude();
+        interpreter
     }
 
-    fn visit_literal_expr(&self, expr: Expr) -> Result<Literal, RunTimeError> {
-        // Changed argument type from `Expr::Literal` to `Expr`
-        if let Expr::Literal { value } = expr {
-            Ok(value)
-        } else {
-            Err(RunTimeError::from(RunTimeError { token: Token {
-                token_type: TokenType::LeftParen,
-                lexeme: "".to_string(),
-                literal: None,
-                line: 0,
-            }, message: "literal".to_string() }))
+    fn run_prelude(&mut self) -> Rc<LoxClass> {
+        let mut scanner = Scanner::new();
+        scanner.scan_and_tokenize(PRELUDE);
+        let statements = Parser::new(scanner.tokens)
+            .parse()
+            .expect("the prelude is valid Lox");
+        Resolver::new(self).resolve(&statements);
+        self.execute(&statements).expect("the prelude runs without errors");
+
+        match self.environment.borrow().lookup("Error") {
+            Some(Value::Class(class)) => class,
+            _ => unreachable!("the prelude defines Error"),
         }
     }
 
-    fn visit_grouping_expr(&self, expr: Expr) -> Result<Literal, RunTimeError> {
+    // instances of `Error` and its subclasses get the line they were created on
+    pub(crate) fn is_error_class(&self, class: &Rc<LoxClass>) -> bool {
+        class.is_subclass_of(&self.error_class)
+    }
+
+    // every module starts from a fresh global scope holding just the built-ins; the `Error`
+    // class is shared so that errors raised in one module can be recognised in another
+    fn new_globals(error_class: &Rc<LoxClass>) -> Rc<RefCell<Environment>> {
+        let mut globals = Environment::new();
+        natives::define_natives(&mut globals);
+        globals.define("Error", Value::Class(Rc::clone(error_class)));
+        Rc::new(RefCell::new(globals))
+    }
+
+    // the file main was given; a module importing it back is reported as a cycle
+    pub(crate) fn set_script(&mut self, path: &str, source: &str) {
+        self.sources.insert(PathBuf::from(path), source.to_string());
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        self.loading = vec![path];
+    }
+
+    // tokens the interpreter made up itself, or scanned from the prelude, have no file to show
+    pub(crate) fn report_error(&self, error: &RunTimeError) {
+        let Some((file, source)) = error
+            .token
+            .file
+            .as_deref()
+            .and_then(|file| Some((file, self.sources.get(file)?)))
+        else {
+            eprintln!("{}", error);
+            return;
+        };
+        let file_name = file.display().to_string();
+        Renderer::new(&file_name, source).emit(&[error.diagnostic()]);
+    }
+
+    pub(crate) fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    fn look_up_variable(&self, id: usize, name: &Token) -> Result<Value, RunTimeError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.environment.borrow().get_global(name),
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &Expr) -> Result<Value, RunTimeError> {
         self.evaluate(expr)
         /*
         if let Expr::Grouping { expression } = expr.clone() {
@@ -57,113 +233,1073 @@ impl Interpreter {
         }*/
     }
 
-    fn evaluate(&self, expr: Expr) -> Result<Literal, RunTimeError> {
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RunTimeError> {
         // Fixed handling of different expression types
         match expr {
             Expr::Binary {
                 left,
                 operator,
                 right,
-            } => self.visit_binary_expression(*left, operator, *right),
-            Expr::Grouping { expression } => self.visit_grouping_expr(*expression),
-            Expr::Literal { value } => Ok(value),
-            Expr::Unary { operator, right } => self.visit_unary_expr(operator, *right),
+                span,
+            } => self.visit_binary_expression(left, operator, right, *span),
+            Expr::Grouping { expression } => self.visit_grouping_expr(expression),
+            Expr::Literal { value } => Ok(Value::from(value.clone())),
+            Expr::Unary {
+                operator,
+                right,
+                span,
+            } => self.visit_unary_expr(operator, right, *span),
+            Expr::Variable { id, name } => self.look_up_variable(*id, name),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => self.visit_logical_expr(left, operator, right),
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.evaluate(condition)?;
+                if self.is_truthy(&condition) {
+                    self.evaluate(then_branch)
+                } else {
+                    self.evaluate(else_branch)
+                }
+            }
+            Expr::Assign {
+                id,
+                name,
+                value,
+                span,
+            } => self.visit_assign_expr(*id, name, value, *span),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                span,
+            } => self.visit_call_expr(callee, paren, arguments, *span),
+            Expr::Get { object, name, span } => {
+                let object = self.evaluate(object)?;
+                let value = match object {
+                    Value::Instance(instance) => LoxInstance::get(&instance, name),
+                    Value::Module(module) => module.get(name),
+                    Value::Enum(enumeration) => enumeration.get(name),
+                    Value::Tagged(value) => value.get(name),
+                    _ => Err(RunTimeError::new(name, "Only instances have properties.")),
+                };
+                value.map_err(|error| error.with_span(*span))
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                span,
+            } => {
+                let object = self.evaluate(object)?;
+                let instance = match object {
+                    Value::Instance(instance) => instance,
+                    Value::Tagged(_) => {
+                        return Err(RunTimeError::new(name, "Enum values are immutable.")
+                            .with_span(*span))
+                    }
+                    _ => {
+                        return Err(
+                            RunTimeError::new(name, "Only instances have fields.").with_span(*span)
+                        )
+                    }
+                };
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(&name.lexeme, value.clone());
+                Ok(value)
+            }
+            Expr::This { id, keyword } => self.look_up_variable(*id, keyword),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => self.visit_super_expr(*id, keyword, method),
+            Expr::List { elements } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(Value::List(Rc::new(RefCell::new(values))))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+                span,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = match object {
+                    Value::List(list) => self
+                        .check_list_index(bracket, &index, list.borrow().len())
+                        .map(|position| list.borrow()[position].clone()),
+                    // a missing key reads as nil, use has() to tell the two apart
+                    Value::Map(map) => self
+                        .check_map_key(bracket, &index)
+                        .map(|key| map.borrow().get(&key).cloned().unwrap_or(Value::Nil)),
+                    _ => Err(RunTimeError::new(bracket, "Only lists and maps can be indexed.")),
+                };
+                value.map_err(|error| error.with_span(*span))
+            }
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+                span,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                let stored = match object {
+                    Value::List(list) => {
+                        let length = list.borrow().len();
+                        self.check_list_index(bracket, &index, length)
+                            .map(|position| list.borrow_mut()[position] = value.clone())
+                    }
+                    Value::Map(map) => self
+                        .check_map_key(bracket, &index)
+                        .map(|key| {
+                            map.borrow_mut().insert(key, value.clone());
+                        }),
+                    _ => Err(RunTimeError::new(bracket, "Only lists and maps can be indexed.")),
+                };
+                stored.map(|_| value).map_err(|error| error.with_span(*span))
+            }
+            Expr::Stringify { expression } => {
+                let value = self.evaluate(expression)?;
+                Ok(Value::String(self.stringify(&Some(value))))
+            }
+            Expr::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                let subject = self.evaluate(subject)?;
+                self.visit_match_expr(keyword, &subject, arms)
+            }
+            Expr::Lambda { declaration } => {
+                let function = LoxFunction {
+                    declaration: Rc::clone(declaration),
+                    closure: Rc::clone(&self.environment),
+                    is_initializer: false,
+                };
+                Ok(Value::Function(Rc::new(function)))
+            }
+            Expr::Map { brace, entries } => {
+                let mut map = LoxMap::new();
+                for (key, value) in entries {
+                    let key = self.evaluate(key)?;
+                    let key = self.check_map_key(brace, &key)?;
+                    let value = self.evaluate(value)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            }
         }
     }
 
-    fn visit_unary_expr(&self, operator: Token, expr: Expr) -> Result<Literal, RunTimeError> {
-        let right = self.evaluate(expr)?;
+    fn import_module(&mut self, keyword: &Token, path: &Token) -> Result<Rc<LoxModule>, RunTimeError> {
+        let Some(Literal::String(relative)) = &path.literal else {
+            unreachable!("the parser only accepts a string literal as the module path")
+        };
+
+        // relative to the file the `import` is written in, even if it only runs once that file
+        // has finished loading
+        let directory = keyword
+            .file
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."));
+        let canonical = fs::canonicalize(directory.join(relative)).map_err(|_| {
+            RunTimeError::new(path, &format!("Could not find module '{}'.", relative))
+        })?;
+
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(Rc::clone(module));
+        }
+
+        if let Some(start) = self.loading.iter().position(|loading| *loading == canonical) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(RunTimeError::new(
+                path,
+                &format!("Cyclic import: {}.", cycle.join(" -> ")),
+            ));
+        }
+
+        let source = fs::read_to_string(&canonical).map_err(|_| {
+            RunTimeError::new(path, &format!("Could not read module '{}'.", relative))
+        })?;
+        let could_not_compile =
+            || RunTimeError::new(keyword, &format!("Could not compile module '{}'.", relative));
+
+        let file_name = canonical.display().to_string();
+        let renderer = Renderer::new(&file_name, &source);
+        self.sources.insert(canonical.clone(), source.clone());
+
+        let mut scanner = Scanner::with_file(&canonical);
+        let scanned = scanner.scan_and_tokenize(&source);
+        renderer.emit(&scanner.diagnostics);
+        if scanned != 0 {
+            return Err(could_not_compile());
+        }
+        let statements = Parser::new(scanner.tokens).parse().map_err(|errors| {
+            parser::report_errors(&renderer, &errors);
+            could_not_compile()
+        })?;
+        let mut resolver = Resolver::new(self);
+        resolver.resolve(&statements);
+        renderer.emit(&resolver.diagnostics);
+        if resolver.had_error {
+            return Err(could_not_compile());
+        }
+
+        let globals = Self::new_globals(&self.error_class);
+        self.loading.push(canonical.clone());
+        let result = self.execute_block(&statements, Rc::clone(&globals));
+        self.loading.pop();
+        match result {
+            Ok(()) => {}
+            Err(Unwind::Error(error)) => return Err(error),
+            // the resolver rejects these outside of functions and loops
+            Err(Unwind::Return(_) | Unwind::Break | Unwind::Continue) => {}
+        }
+
+        let name = canonical
+            .file_stem()
+            .map_or_else(|| relative.clone(), |stem| stem.to_string_lossy().into_owned());
+        let exports = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Stmt::Export { declaration } => declaration.declared_name(),
+                _ => None,
+            })
+            .map(|name| name.lexeme.clone())
+            .collect();
+
+        let module = Rc::new(LoxModule {
+            name,
+            globals,
+            exports,
+        });
+        self.modules.insert(canonical, Rc::clone(&module));
+        Ok(module)
+    }
+
+    // a thrown value comes back as is, anything else becomes an `Error` with its message and line
+    fn caught_value(&self, error: &RunTimeError) -> Value {
+        if let Some(thrown) = &error.thrown {
+            return thrown.clone();
+        }
+
+        let mut instance = LoxInstance::new(Rc::clone(&self.error_class));
+        instance.set("message", Value::String(error.message.clone()));
+        instance.set("line", Value::Int(error.token.line as i64));
+        Value::Instance(Rc::new(RefCell::new(instance)))
+    }
+
+    // what an uncaught `throw` reports, an error object's own message if it has one
+    fn thrown_message(&self, value: &Value) -> String {
+        if let Value::Instance(instance) = value {
+            if let Some(Value::String(message)) = instance.borrow().field("message") {
+                return message;
+            }
+        }
+        format!("Uncaught {}", self.stringify(&Some(value.clone())))
+    }
+
+    // arms are tried in order; an arm's bindings only become visible once its whole pattern
+    // has matched, and its guard and body see them in a scope of their own
+    fn visit_match_expr(
+        &mut self,
+        keyword: &Token,
+        subject: &Value,
+        arms: &[MatchArm],
+    ) -> Result<Value, RunTimeError> {
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !self.match_pattern(&arm.pattern, subject, &mut bindings)? {
+                continue;
+            }
+
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            for (name, value) in bindings {
+                environment.define(&name, value);
+            }
+            let environment = Rc::new(RefCell::new(environment));
+
+            if let Some(guard) = &arm.guard {
+                let guard = self.evaluate_in(guard, Rc::clone(&environment))?;
+                if !self.is_truthy(&guard) {
+                    continue;
+                }
+            }
+            return self.evaluate_in(&arm.body, environment);
+        }
+
+        Err(RunTimeError::new(
+            keyword,
+            &format!("No match arm matched {}.", self.stringify_nested(subject, &mut Vec::new())),
+        ))
+    }
+
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool, RunTimeError> {
+        match pattern {
+            Pattern::Wildcard => Ok(true),
+            Pattern::Binding { name } => {
+                bindings.push((name.lexeme.clone(), value.clone()));
+                Ok(true)
+            }
+            Pattern::Literal { value: literal } => {
+                Ok(self.is_equal(&Value::from(literal.clone()), value))
+            }
+            Pattern::List { elements, rest } => {
+                let Value::List(list) = value else {
+                    return Ok(false);
+                };
+                // copied out so matching nested patterns can't hold the borrow
+                let items = list.borrow().clone();
+                let length_fits = match rest {
+                    Some(_) => items.len() >= elements.len(),
+                    None => items.len() == elements.len(),
+                };
+                if !length_fits {
+                    return Ok(false);
+                }
+                for (element, item) in elements.iter().zip(&items) {
+                    if !self.match_pattern(element, item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                match rest {
+                    Some(rest) => {
+                        let remaining = items[elements.len()..].to_vec();
+                        self.match_pattern(rest, &Value::List(Rc::new(RefCell::new(remaining))), bindings)
+                    }
+                    None => Ok(true),
+                }
+            }
+            Pattern::Map { entries } => {
+                let Value::Map(map) = value else {
+                    return Ok(false);
+                };
+                for (key, entry) in entries {
+                    let Ok(key) = MapKey::from_value(&Value::from(key.clone())) else {
+                        return Ok(false);
+                    };
+                    let Some(item) = map.borrow().get(&key).cloned() else {
+                        return Ok(false);
+                    };
+                    if !self.match_pattern(entry, &item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::Instance { class, fields } => {
+                let Value::Class(class) = self.evaluate(class)? else {
+                    let Expr::Variable { name, .. } = class else {
+                        unreachable!("the parser only produces variables as pattern classes")
+                    };
+                    return Err(RunTimeError::new(name, "Pattern must name a class."));
+                };
+                let Value::Instance(instance) = value else {
+                    return Ok(false);
+                };
+                if !instance.borrow().class.is_subclass_of(&class) {
+                    return Ok(false);
+                }
+                for (name, field) in fields {
+                    let Some(item) = instance.borrow().field(&name.lexeme) else {
+                        return Ok(false);
+                    };
+                    if !self.match_pattern(field, &item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::Variant {
+                enumeration,
+                variant,
+                fields,
+            } => {
+                let Value::Enum(enumeration) = self.evaluate(enumeration)? else {
+                    return Err(RunTimeError::new(variant, "Pattern must name an enum."));
+                };
+                let Some(expected) = enumeration.find_variant(&variant.lexeme) else {
+                    return Err(RunTimeError::new(
+                        variant,
+                        &format!(
+                            "Undefined variant '{}' of enum {}.",
+                            variant.lexeme, enumeration.name
+                        ),
+                    ));
+                };
+                let Value::Tagged(value) = value else {
+                    return Ok(false);
+                };
+                if !Rc::ptr_eq(&value.variant, &expected) {
+                    return Ok(false);
+                }
+                let Some(fields) = fields else {
+                    return Ok(true);
+                };
+                if fields.len() != expected.fields.len() {
+                    return Err(RunTimeError::new(
+                        variant,
+                        &format!(
+                            "Variant {}.{} has {} fields but the pattern has {}.",
+                            enumeration.name,
+                            expected.name,
+                            expected.fields.len(),
+                            fields.len()
+                        ),
+                    ));
+                }
+                for (field, item) in fields.iter().zip(&value.values) {
+                    if !self.match_pattern(field, item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn evaluate_in(
+        &mut self,
+        expr: &Expr,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<Value, RunTimeError> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.evaluate(expr);
+        self.environment = previous;
+        result
+    }
+
+    fn check_map_key(&self, token: &Token, key: &Value) -> Result<MapKey, RunTimeError> {
+        MapKey::from_value(key).map_err(|message| RunTimeError::new(token, message))
+    }
+
+    fn check_list_index(
+        &self,
+        bracket: &Token,
+        index: &Value,
+        len: usize,
+    ) -> Result<usize, RunTimeError> {
+        match index {
+            Value::Int(n) => usize::try_from(*n)
+                .ok()
+                .filter(|position| *position < len)
+                .ok_or_else(|| {
+                    RunTimeError::new(
+                        bracket,
+                        &format!("List index {} out of range for length {}.", n, len),
+                    )
+                }),
+            _ => Err(RunTimeError::new(bracket, "List index must be an integer.")),
+        }
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        id: usize,
+        keyword: &Token,
+        method: &Token,
+    ) -> Result<Value, RunTimeError> {
+        // the resolver always resolves `super`; `this` lives in the scope just inside it
+        let distance = self.locals.get(&id).copied().unwrap_or(0);
+        let superclass = self.environment.borrow().get_at(distance, keyword)?;
+        let this = Token {
+            token_type: TokenType::This,
+            lexeme: "this".to_string(),
+            ..keyword.clone()
+        };
+        let object = self.environment.borrow().get_at(distance - 1, &this)?;
+
+        let (Value::Class(superclass), Value::Instance(instance)) = (superclass, object) else {
+            return Err(RunTimeError::new(keyword, "Invalid 'super' binding."));
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+            None => Err(RunTimeError::new(
+                method,
+                &format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
+
+    // errors about the call itself cover all of it, ones raised inside the callee are left alone
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+        span: Span,
+    ) -> Result<Value, RunTimeError> {
+        let callee = self.evaluate(callee)?;
+
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.evaluate(argument)?);
+        }
+
+        let function: Rc<dyn Callable> = match callee {
+            Value::Function(function) => function,
+            Value::NativeFunction(function) => function,
+            Value::Class(class) => class,
+            Value::Variant(variant) => variant,
+            _ => {
+                return Err(RunTimeError::new(
+                    paren,
+                    "Can only call functions and classes.",
+                )
+                .with_span(span))
+            }
+        };
+
+        if values.len() != function.arity() {
+            return Err(RunTimeError::new(
+                paren,
+                &format!(
+                    "Expected {} arguments but got {}.",
+                    function.arity(),
+                    values.len()
+                ),
+            )
+            .with_span(span));
+        }
+
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(RunTimeError::new(paren, "Stack overflow.").with_span(span));
+        }
+        self.call_depth += 1;
+        let result = function.call(self, values, paren);
+        self.call_depth -= 1;
+        result
+    }
 
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<Value, RunTimeError> {
+        let left = self.evaluate(left)?;
+
+        // short-circuit and hand back the operand itself rather than a coerced bool
         match operator.token_type {
-            TokenType::Minus => {
-                let value = self.check_number_operand(&operator, Some(right.clone()))?;
-                Ok(Literal::Number(-value)) // Correctly return a `Literal`
+            TokenType::Or if self.is_truthy(&left) => return Ok(left),
+            TokenType::And if !self.is_truthy(&left) => return Ok(left),
+            // `??` only falls through to the right side for nil, false is kept
+            TokenType::QuestionQuestion if !matches!(left, Value::Nil) => return Ok(left),
+            _ => {}
+        }
+
+        self.evaluate(right)
+    }
+
+    fn visit_assign_expr(
+        &mut self,
+        id: usize,
+        name: &Token,
+        value: &Expr,
+        span: Span,
+    ) -> Result<Value, RunTimeError> {
+        let value = self.evaluate(value)?;
+        let assigned = match self.locals.get(&id) {
+            Some(distance) => self
+                .environment
+                .borrow_mut()
+                .assign_at(*distance, name, value.clone()),
+            None => self
+                .environment
+                .borrow_mut()
+                .assign_global(name, value.clone()),
+        };
+        assigned
+            .map(|_| value)
+            .map_err(|error| error.with_span(span))
+    }
+
+    fn execute_statement(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match stmt {
+            Stmt::Expression { expression } => {
+                self.evaluate(expression)?;
+            }
+            Stmt::Print { expression } => {
+                let value = self.evaluate(expression)?;
+                println!("{}", self.stringify(&Some(value)));
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(&name.lexeme, value);
+            }
+            Stmt::Function { declaration } => {
+                let function = LoxFunction {
+                    declaration: Rc::clone(declaration),
+                    closure: Rc::clone(&self.environment),
+                    is_initializer: false,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(&declaration.name.lexeme, Value::Function(Rc::new(function)));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass_expr) => match self.evaluate(superclass_expr)? {
+                        Value::Class(class) => Some(class),
+                        _ => {
+                            let Expr::Variable { name, .. } = superclass_expr else {
+                                unreachable!("the parser only produces variables as superclasses")
+                            };
+                            return Err(RunTimeError::new(name, "Superclass must be a class.").into());
+                        }
+                    },
+                    None => None,
+                };
+
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Nil);
+
+                // methods of a subclass close over an extra scope that binds `super`
+                let enclosing = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                    environment.define("super", Value::Class(Rc::clone(superclass)));
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        let function = LoxFunction {
+                            declaration: Rc::clone(method),
+                            closure: Rc::clone(&self.environment),
+                            is_initializer: method.name.lexeme == "init",
+                        };
+                        (method.name.lexeme.clone(), Rc::new(function))
+                    })
+                    .collect();
+
+                let class = LoxClass {
+                    name: name.lexeme.clone(),
+                    superclass,
+                    methods,
+                };
+
+                self.environment = enclosing;
+                self.environment
+                    .borrow_mut()
+                    .assign(name, Value::Class(Rc::new(class)))?;
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Block { statements } => {
+                let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.evaluate(condition)?;
+                if self.is_truthy(&condition) {
+                    self.execute_statement(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute_statement(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                loop {
+                    let value = self.evaluate(condition)?;
+                    if !self.is_truthy(&value) {
+                        break;
+                    }
+                    match self.execute_statement(body) {
+                        Ok(()) | Err(Unwind::Continue) => {}
+                        Err(Unwind::Break) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
+                }
+            }
+            Stmt::Enum { name, variants } => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, fields)| {
+                        Rc::new(LoxVariant {
+                            enum_name: name.lexeme.clone(),
+                            name: variant.lexeme.clone(),
+                            fields: fields.iter().map(|field| field.lexeme.clone()).collect(),
+                        })
+                    })
+                    .collect();
+                let enumeration = LoxEnum {
+                    name: name.lexeme.clone(),
+                    variants,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Enum(Rc::new(enumeration)));
             }
-            TokenType::Bang => Ok(Literal::Bool(!self.is_truthy(&right))),
-            _ => Err(RunTimeError::new(&operator, "Invalid unary operator")),
+            Stmt::Import {
+                keyword,
+                path,
+                name,
+            } => {
+                let module = self.import_module(keyword, path)?;
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Module(module));
+            }
+            // which names are exported was worked out when the module was loaded
+            Stmt::Export { declaration } => self.execute_statement(declaration)?,
+            Stmt::Throw { keyword, value } => {
+                let value = self.evaluate(value)?;
+                let error = RunTimeError {
+                    token: Box::new(keyword.clone()),
+                    message: self.thrown_message(&value),
+                    thrown: Some(value),
+                };
+                return Err(error.into());
+            }
+            Stmt::Try {
+                body,
+                handler,
+                finally,
+            } => {
+                let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                let mut result = self.execute_block(body, Rc::new(RefCell::new(environment)));
+
+                // only errors are caught, `return`, `break` and `continue` pass straight through
+                if let (Err(Unwind::Error(error)), Some((name, handler_body))) = (&result, handler) {
+                    let caught = self.caught_value(error);
+                    let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                    environment.define(&name.lexeme, caught);
+                    result = self.execute_block(handler_body, Rc::new(RefCell::new(environment)));
+                }
+
+                // runs however the try or catch block ended, and anything it unwinds with
+                // itself replaces whatever was already unwinding
+                if let Some(finally) = finally {
+                    let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                    self.execute_block(finally, Rc::new(RefCell::new(environment)))?;
+                }
+
+                return result;
+            }
+            Stmt::Break => return Err(Unwind::Break),
+            Stmt::Continue => return Err(Unwind::Continue),
         }
+        Ok(())
     }
 
-    fn is_truthy(&self, literal: &Literal) -> bool {
-        match literal {
-            Literal::Bool(b) => *b, // Fixed handling for literal boolean
-            Literal::Nil => false,  // Handle Nil explicitly
+    pub(crate) fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute_statement(statement));
+        // restore the outer scope even if the block bailed out with an error
+        self.environment = previous;
+        result
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        operator: &Token,
+        expr: &Expr,
+        span: Span,
+    ) -> Result<Value, RunTimeError> {
+        let right = self.evaluate(expr)?;
+
+        // as with binary operators, a bad operand is reported against the whole expression
+        let result = match operator.token_type {
+            TokenType::Minus => match right {
+                Value::Int(value) => self.check_overflow(operator, value.checked_neg()),
+                right => self
+                    .check_number_operand(operator, Some(right))
+                    .map(|value| Value::Number(-value)),
+            },
+            TokenType::Tilde => match right {
+                Value::Int(value) => Ok(Value::Int(!value)),
+                _ => Err(RunTimeError::new(operator, "Operand must be an integer")),
+            },
+            TokenType::Bang => Ok(Value::Bool(!self.is_truthy(&right))),
+            _ => Err(RunTimeError::new(operator, "Invalid unary operator")),
+        };
+        result.map_err(|error| error.with_span(span))
+    }
+
+    fn is_truthy(&self, value: &Value) -> bool {
+        match value {
+            Value::Bool(b) => *b, // Fixed handling for literal boolean
+            Value::Nil => false,  // Handle Nil explicitly
             _ => true,
         }
     }
 
     fn visit_binary_expression(
-        &self,
-        left_expr: Expr,
-        operator: Token,
-        right_expr: Expr,
-    ) -> Result<Literal, RunTimeError> {
+        &mut self,
+        left_expr: &Expr,
+        operator: &Token,
+        right_expr: &Expr,
+        span: Span,
+    ) -> Result<Value, RunTimeError> {
         // Reorganized arguments to handle Expr structs
 
         let left = self.evaluate(left_expr)?;
         let right = self.evaluate(right_expr)?;
 
+        // anything that goes wrong from here on is down to both operands, not just the operator
+        self.binary_operation(operator, left, right)
+            .map_err(|error| error.with_span(span))
+    }
+
+    /*
+    Numeric rules: two ints stay an int, anything mixed with a float is promoted to a float.
+    Integer arithmetic that overflows i64 is a runtime error rather than wrapping. `/` is
+    always true division and yields a float, `~/` floors and keeps ints as ints.
+     */
+    fn binary_operation(
+        &self,
+        operator: &Token,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RunTimeError> {
         match operator.token_type {
-            TokenType::Greater => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Bool(left_val > right_val))
-            }
-            TokenType::GreaterEqual => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Bool(left_val >= right_val))
-            }
-            TokenType::Less => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Bool(left_val < right_val))
-            }
-            TokenType::LessEqual => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Bool(left_val <= right_val))
-            }
-            TokenType::Minus => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Number(left_val - right_val))
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                let ordering = match (&left, &right) {
+                    (Value::Int(l), Value::Int(r)) => l.partial_cmp(r),
+                    // exact, like equality, so 2 ** 53 + 1 > 2.0 ** 53 holds
+                    (Value::Int(l), Value::Number(r)) => compare_int_float(*l, *r),
+                    (Value::Number(l), Value::Int(r)) => {
+                        compare_int_float(*r, *l).map(Ordering::reverse)
+                    }
+                    _ => {
+                        let (left_val, right_val) =
+                            self.check_number_operands(operator, &left, &right)?;
+                        left_val.partial_cmp(&right_val)
+                    }
+                };
+                // NaN compares false against everything
+                Ok(Value::Bool(ordering.is_some_and(|ordering| match operator.token_type {
+                    TokenType::Greater => ordering.is_gt(),
+                    TokenType::GreaterEqual => ordering.is_ge(),
+                    TokenType::Less => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                })))
             }
+            TokenType::Minus => match (&left, &right) {
+                (Value::Int(l), Value::Int(r)) => self.check_overflow(operator, l.checked_sub(*r)),
+                _ => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    Ok(Value::Number(left_val - right_val))
+                }
+            },
             TokenType::Slash => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
+                let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
                 if right_val == 0.0 {
-                    return Err(RunTimeError::new(&operator,"Division by zero")); // Handle divide by zero
+                    return Err(RunTimeError::new(operator,"Division by zero")); // Handle divide by zero
                 }
-                Ok(Literal::Number(left_val / right_val))
-            }
-            TokenType::Star => {
-                let (left_val, right_val) = self.check_number_operands(&operator, &left, &right)?;
-                Ok(Literal::Number(left_val * right_val))
+                Ok(Value::Number(left_val / right_val))
             }
+            TokenType::Star => match (&left, &right) {
+                (Value::Int(l), Value::Int(r)) => self.check_overflow(operator, l.checked_mul(*r)),
+                _ => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    Ok(Value::Number(left_val * right_val))
+                }
+            },
+            // floored: the result takes the sign of the divisor, so that
+            // a == (a ~/ b) * b + a % b holds for every sign combination
+            TokenType::Percent => match (&left, &right) {
+                (Value::Int(_), Value::Int(0)) => Err(RunTimeError::new(operator, "Modulo by zero")),
+                (Value::Int(l), Value::Int(r)) => {
+                    let remainder = self.check_overflow(operator, l.checked_rem(*r))?;
+                    match remainder {
+                        Value::Int(m) if m != 0 && (m < 0) != (*r < 0) => Ok(Value::Int(m + r)),
+                        remainder => Ok(remainder),
+                    }
+                }
+                _ => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    if right_val == 0.0 {
+                        return Err(RunTimeError::new(operator, "Modulo by zero"));
+                    }
+                    let remainder = left_val % right_val;
+                    if remainder != 0.0 && (remainder < 0.0) != (right_val < 0.0) {
+                        Ok(Value::Number(remainder + right_val))
+                    } else {
+                        Ok(Value::Number(remainder))
+                    }
+                }
+            },
+            TokenType::TildeSlash => match (&left, &right) {
+                (Value::Int(_), Value::Int(0)) => Err(RunTimeError::new(operator, "Division by zero")),
+                (Value::Int(l), Value::Int(r)) => {
+                    let quotient = self.check_overflow(operator, l.checked_div(*r))?;
+                    match quotient {
+                        // checked_div truncates toward zero, step down when the signs differ
+                        Value::Int(q) if l % r != 0 && (*l < 0) != (*r < 0) => Ok(Value::Int(q - 1)),
+                        quotient => Ok(quotient),
+                    }
+                }
+                _ => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    if right_val == 0.0 {
+                        return Err(RunTimeError::new(operator, "Division by zero"));
+                    }
+                    Ok(Value::Number((left_val / right_val).floor()))
+                }
+            },
+            TokenType::StarStar => match (&left, &right) {
+                // a negative exponent can't produce an int, fall through to floats for it
+                (Value::Int(l), Value::Int(r)) if *r >= 0 => {
+                    let result = u32::try_from(*r).ok().and_then(|r| l.checked_pow(r));
+                    self.check_overflow(operator, result)
+                }
+                _ => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    Ok(Value::Number(left_val.powf(right_val)))
+                }
+            },
             TokenType::Plus => match (&left, &right) {
-                (Literal::Number(l), Literal::Number(r)) => Ok(Literal::Number(l + r)),
-                (Literal::String(l), Literal::String(r)) => {
-                    Ok(Literal::String(format!("{}{}", l, r))) // Concatenate strings correctly
+                (Value::Int(l), Value::Int(r)) => self.check_overflow(operator, l.checked_add(*r)),
+                (Value::Int(_) | Value::Number(_), Value::Int(_) | Value::Number(_)) => {
+                    let (left_val, right_val) = self.check_number_operands(operator, &left, &right)?;
+                    Ok(Value::Number(left_val + right_val))
+                }
+                (Value::String(l), Value::String(r)) => {
+                    Ok(Value::String(format!("{}{}", l, r))) // Concatenate strings correctly
                 }
                 _ => Err(RunTimeError::new(
-                    &operator,
+                    operator,
                     "Operands must be two numbers or strings",
                 )
                     ),
             },
-            TokenType::BangEqual => Ok(Literal::Bool(!self.is_equal(&left, &right))),
-            TokenType::EqualEqual => Ok(Literal::Bool(self.is_equal(&left, &right))),
-            _ => Err(RunTimeError::new(&operator, "Unknown binary operator")),
+            TokenType::Ampersand => {
+                let (left_val, right_val) = self.check_int_operands(operator, &left, &right)?;
+                Ok(Value::Int(left_val & right_val))
+            }
+            TokenType::Pipe => {
+                let (left_val, right_val) = self.check_int_operands(operator, &left, &right)?;
+                Ok(Value::Int(left_val | right_val))
+            }
+            TokenType::Caret => {
+                let (left_val, right_val) = self.check_int_operands(operator, &left, &right)?;
+                Ok(Value::Int(left_val ^ right_val))
+            }
+            TokenType::LessLess => {
+                let (left_val, right_val) = self.check_int_operands(operator, &left, &right)?;
+                let shift = self.check_shift_amount(operator, right_val)?;
+                let result = left_val << shift;
+                // shifting bits out of the top, sign bit included, counts as overflow
+                if result >> shift != left_val {
+                    return Err(RunTimeError::new(operator, "Integer overflow"));
+                }
+                Ok(Value::Int(result))
+            }
+            TokenType::GreaterGreater => {
+                let (left_val, right_val) = self.check_int_operands(operator, &left, &right)?;
+                let shift = self.check_shift_amount(operator, right_val)?;
+                Ok(Value::Int(left_val >> shift))
+            }
+            TokenType::BangEqual => Ok(Value::Bool(!self.is_equal(&left, &right))),
+            TokenType::EqualEqual => Ok(Value::Bool(self.is_equal(&left, &right))),
+            _ => Err(RunTimeError::new(operator, "Unknown binary operator")),
         }
     }
 
-    fn is_equal(&self, left: &Literal, right: &Literal) -> bool {
+    fn is_equal(&self, left: &Value, right: &Value) -> bool {
         // Handle equality checking correctly
-        left == right
+        match (left, right) {
+            (Value::Int(l), Value::Int(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            // compared exactly, 2 ** 53 + 1 must not equal the float it would round to
+            (Value::Int(i), Value::Number(n)) | (Value::Number(n), Value::Int(i)) => {
+                float_to_int(*n) == Some(*i)
+            }
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            // functions only ever equal themselves
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::List(l), Value::List(r)) => Rc::ptr_eq(l, r),
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+            (Value::Module(l), Value::Module(r)) => Rc::ptr_eq(l, r),
+            (Value::Enum(l), Value::Enum(r)) => Rc::ptr_eq(l, r),
+            (Value::Variant(l), Value::Variant(r)) => Rc::ptr_eq(l, r),
+            // enum values are immutable, so two with the same variant and fields are the same value
+            (Value::Tagged(l), Value::Tagged(r)) => {
+                Rc::ptr_eq(&l.variant, &r.variant)
+                    && l
+                        .values
+                        .iter()
+                        .zip(&r.values)
+                        .all(|(l, r)| self.is_equal(l, r))
+            }
+            _ => false,
+        }
     }
 
     fn check_number_operand(
         &self,
         operator: &Token,
-        operand: Option<Literal>,
+        operand: Option<Value>,
     ) -> Result<f64, RunTimeError> {
         match operand {
-            Some(Literal::Number(x)) => Ok(x),
+            Some(Value::Number(x)) => Ok(x),
+            Some(Value::Int(x)) => Ok(x as f64),
             _ => Err(RunTimeError::new(
                 operator,
                 "Operand must be a number",
@@ -171,14 +1307,18 @@ impl Interpreter {
         }
     }
 
+    // accepts ints as well, promoting them to floats
     fn check_number_operands(
         &self,
         operator: &Token,
-        left: &Literal,
-        right: &Literal,
+        left: &Value,
+        right: &Value,
     ) -> Result<(f64, f64), RunTimeError> {
         match (left, right) {
-            (Literal::Number(x), Literal::Number(y)) => Ok((*x, *y)),
+            (Value::Number(x), Value::Number(y)) => Ok((*x, *y)),
+            (Value::Int(x), Value::Number(y)) => Ok((*x as f64, *y)),
+            (Value::Number(x), Value::Int(y)) => Ok((*x, *y as f64)),
+            (Value::Int(x), Value::Int(y)) => Ok((*x as f64, *y as f64)),
             _ => Err(RunTimeError::new(
                 operator,
                 "Operands must be numbers",
@@ -186,35 +1326,171 @@ impl Interpreter {
         }
     }
 
-    pub(crate) fn interpret(&self, expression: Expr) -> Result<String, RunTimeError> {
-        match self.evaluate(expression) {
-            Ok(result) => {
-                return Ok(self.stringify(&Some(result)))
+    fn check_int_operands(
+        &self,
+        operator: &Token,
+        left: &Value,
+        right: &Value,
+    ) -> Result<(i64, i64), RunTimeError> {
+        match (left, right) {
+            (Value::Int(x), Value::Int(y)) => Ok((*x, *y)),
+            _ => Err(RunTimeError::new(
+                operator,
+                "Operands must be integers",
+            )),
+        }
+    }
 
-            },
-            Err(err) => Err(RunTimeError::new(&Token {
-                token_type: TokenType::LeftParen,
-                lexeme: "".to_string(),
-                literal: None,
-                line: 0,
-            }, "RunTimeError")), // Print error message correctly
+    fn check_shift_amount(&self, operator: &Token, amount: i64) -> Result<u32, RunTimeError> {
+        match u32::try_from(amount) {
+            Ok(amount) if amount < i64::BITS => Ok(amount),
+            _ => Err(RunTimeError::new(
+                operator,
+                "Shift amount must be between 0 and 63",
+            )),
         }
     }
 
-    fn stringify(&self, literal: &Option<Literal>) -> String {
-        match literal {
+    fn check_overflow(&self, operator: &Token, result: Option<i64>) -> Result<Value, RunTimeError> {
+        result
+            .map(Value::Int)
+            .ok_or_else(|| RunTimeError::new(operator, "Integer overflow"))
+    }
+
+    pub(crate) fn interpret(&mut self, expression: &Expr) -> Result<String, RunTimeError> {
+        let result = self.evaluate(expression)?;
+        Ok(self.stringify(&Some(result)))
+    }
+
+    pub(crate) fn execute(&mut self, statements: &[Stmt]) -> Result<(), RunTimeError> {
+        for statement in statements {
+            match self.execute_statement(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(error),
+                // the resolver and parser reject these outside a function or loop
+                Err(Unwind::Return(_) | Unwind::Break | Unwind::Continue) => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn stringify(&self, value: &Option<Value>) -> String {
+        match value {
             None => "nil".to_string(), // Use to_string for String conversion
-            Some(Literal::Number(n)) => {
-                let text = n.to_string();
-                if text.ends_with(".0") {
-                    text.strip_suffix(".0").unwrap().to_string()
-                } else {
-                    text
+            Some(value) => self.stringify_value(value, &mut Vec::new()),
+        }
+    }
+
+    // `printing` holds the lists and maps already being printed further up, so a collection
+    // that contains itself shows up as `[...]` or `{...}` instead of recursing forever
+    fn stringify_value(&self, value: &Value, printing: &mut Vec<*const ()>) -> String {
+        match value {
+            Value::Int(n) => n.to_string(),
+            // floats always keep their decimal point so they can't be mistaken for ints
+            Value::Number(n) => format!("{:?}", n),
+            Value::String(s) => s.clone(),
+            Value::Bool(b) => b.to_string(),
+            Value::Nil => "nil".to_string(),
+            Value::Function(function) => format!("<fn {}>", function.declaration.name.lexeme),
+            Value::NativeFunction(function) => format!("<native fn {}>", function.name),
+            Value::Class(class) => class.name.clone(),
+            Value::Instance(instance) => format!("{} instance", instance.borrow().class.name),
+            Value::Module(module) => format!("<module {}>", module.name),
+            Value::Enum(enumeration) => enumeration.name.clone(),
+            Value::Variant(variant) => {
+                format!("<variant {}.{}>", variant.enum_name, variant.name)
+            }
+            Value::Tagged(value) if value.values.is_empty() => {
+                format!("{}.{}", value.variant.enum_name, value.variant.name)
+            }
+            Value::Tagged(value) => {
+                let fields: Vec<String> = value
+                    .values
+                    .iter()
+                    .map(|field| self.stringify_nested(field, printing))
+                    .collect();
+                format!(
+                    "{}.{}({})",
+                    value.variant.enum_name,
+                    value.variant.name,
+                    fields.join(", ")
+                )
+            }
+            Value::List(list) => {
+                let id = Rc::as_ptr(list) as *const ();
+                if printing.contains(&id) {
+                    return "[...]".to_string();
+                }
+                printing.push(id);
+                let elements: Vec<String> = list
+                    .borrow()
+                    .iter()
+                    .map(|element| self.stringify_nested(element, printing))
+                    .collect();
+                printing.pop();
+                format!("[{}]", elements.join(", "))
+            }
+            Value::Map(map) => {
+                let id = Rc::as_ptr(map) as *const ();
+                if printing.contains(&id) {
+                    return "{...}".to_string();
                 }
+                printing.push(id);
+                let entries: Vec<String> = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            self.stringify_nested(&key.to_value(), printing),
+                            self.stringify_nested(value, printing)
+                        )
+                    })
+                    .collect();
+                printing.pop();
+                format!("{{{}}}", entries.join(", "))
             }
-            Some(Literal::String(s)) => s.clone(),
-            Some(Literal::Bool(b)) => b.to_string(),
-            Some(Literal::Nil) => "nil".to_string(),
         }
     }
-}
\ No newline at end of file
+
+    // quote strings inside collections so ["1"] and [1] print differently
+    fn stringify_nested(&self, value: &Value, printing: &mut Vec<*const ()>) -> String {
+        match value {
+            Value::String(s) => format!("\"{}\"", s),
+            value => self.stringify_value(value, printing),
+        }
+    }
+}
+
+// the int a float is exactly equal to, if there is one
+pub(crate) fn float_to_int(n: f64) -> Option<i64> {
+    // i64::MAX isn't representable as a float, the bound is 2 ** 63 exclusive
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        Some(n as i64)
+    } else {
+        None
+    }
+}
+
+// how an int orders against a float without rounding the int, None if the float is NaN
+fn compare_int_float(i: i64, n: f64) -> Option<Ordering> {
+    if n.is_nan() {
+        None
+    } else if n >= i64::MAX as f64 {
+        Some(Ordering::Less)
+    } else if n < i64::MIN as f64 {
+        Some(Ordering::Greater)
+    } else {
+        // the whole part fits an i64 exactly, the fraction only matters on a tie
+        let whole = n.trunc();
+        let fraction = n - whole;
+        let tie = if fraction > 0.0 {
+            Ordering::Less
+        } else if fraction < 0.0 {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        };
+        Some(i.cmp(&(whole as i64)).then(tie))
+    }
+}
@@ -1,21 +1,45 @@
 mod scanner;
+mod diagnostic;
 mod parser;
 mod evaluator;
+mod environment;
+mod callable;
+mod class;
+mod enums;
+mod resolver;
+mod natives;
+mod map;
+mod module;
 
 use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::path::Path;
 use std::process::exit;
-use std::string::ParseError;
+use std::thread;
 use scanner::Scanner;
-use parser::Parser;
+use crate::diagnostic::Renderer;
 use crate::evaluator::Interpreter;
-use crate::scanner::{Literal, Token, TokenType};
+use crate::resolver::Resolver;
+
+// Every Lox call recurses through the evaluator on the native stack, so the main thread's
+// default is nowhere near enough to reach the interpreter's own call depth limit
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
+    let command = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command)
+        .expect("failed to start the interpreter thread");
+    // a panic has already been reported by the thread itself
+    if command.join().is_err() {
+        exit(101);
+    }
+}
+
+fn run_command() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+        eprintln!("Usage: {} <tokenize|parse|evaluate|run> <filename>", args[0]);
         return;
     }
 
@@ -24,20 +48,19 @@ fn main() {
 
     match command.as_str() {
         "tokenize" => {
-            writeln!(io::stderr(), "Logs from your program will appear here!").unwrap();
+            eprintln!("Logs from your program will appear here!");
 
             let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
+                eprintln!("Failed to read file {}", filename);
                 String::new()
             });
-//TODO: implement the "parse" command
             if !file_contents.is_empty() {
-                let mut scanner = Scanner::new();
+                let mut scanner = Scanner::with_file(Path::new(filename));
                 let return_code = scanner.scan_and_tokenize(&file_contents);
+                Renderer::new(filename, &file_contents).emit(&scanner.diagnostics);
                 for token in scanner.tokens{
                     println!("{}", token)
                 }
-                println!("EOF  null");
                 if return_code != 0 {
                     exit(return_code);
                 }
@@ -48,22 +71,21 @@ fn main() {
         }
         "parse" => {
             let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
+                eprintln!("Failed to read file {}", filename);
                 String::new()
             });
             if !file_contents.is_empty() {
-                let mut scanner = Scanner::new();
+                let renderer = Renderer::new(filename, &file_contents);
+                let mut scanner = Scanner::with_file(Path::new(filename));
                 scanner.scan_and_tokenize(&file_contents);
+                renderer.emit(&scanner.diagnostics);
                 let mut parsed_file = parser::Parser::new(scanner.tokens);
-                let result = parsed_file.parse();
-                if result.is_ok() {
-                    for token in result{
-                        println!("{}", token) // var and fun and that lot arnt actyaly parsed yet so wejsut need to regurgiatete the tokens
-
+                match parsed_file.parse_expression() {
+                    Ok(expression) => println!("{}", expression),
+                    Err(errors) => {
+                        parser::report_errors(&renderer, &errors);
+                        exit(65)
                     }
-
-                }else{
-                    exit(65)
                 }
             } else {
                 println!("EOF  null")
@@ -71,61 +93,73 @@ fn main() {
         },
         "evaluate" => {
             let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
+                eprintln!("Failed to read file {}", filename);
                 String::new()
             });
             if !file_contents.is_empty() {
-                let mut scanner = Scanner::new();
+                let renderer = Renderer::new(filename, &file_contents);
+                let mut scanner = Scanner::with_file(Path::new(filename));
                 scanner.scan_and_tokenize(&file_contents);
+                renderer.emit(&scanner.diagnostics);
                 let mut parsed_file = parser::Parser::new(scanner.tokens);
-                let result = parsed_file.parse();
-                let interpreter = Interpreter::new();
-                if result.is_ok() {
-                    /*if let out = interpreter.interpret(result.clone().unwrap()){
-                        match out {
-                            Ok(T) => {
-                                println!("{:?}", out);
-                                exit(0)
-                            },
-                            Err(e)=> exit(70),
+                let result = parsed_file.parse_expression();
+                let mut interpreter = Interpreter::new();
+                interpreter.set_script(filename, &file_contents);
+                match result {
+                    Ok(expression) => match interpreter.interpret(&expression) {
+                        Ok(value) => println!("{}", value),
+                        Err(error) => {
+                            interpreter.report_error(&error);
+                            exit(70)
                         }
-                    }*/
-                    let out = match interpreter.interpret(result.unwrap()) {
-                        Ok(T) => {
-                            println!("{}", T);
-                        },
-                        Err(e)=> exit(70),
-                    };
-                    exit(0)
-
-                }else{
-                    exit(65)
+                    },
+                    Err(errors) => {
+                        parser::report_errors(&renderer, &errors);
+                        exit(65)
+                    }
                 }
             } else {
                 println!("EOF  null")
             }
         }
+        "run" => {
+            // unlike the other commands there is nothing useful to do with an empty program
+            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
+                eprintln!("Failed to read file {}", filename);
+                exit(66)
+            });
+            let renderer = Renderer::new(filename, &file_contents);
+            let mut scanner = Scanner::with_file(Path::new(filename));
+            let return_code = scanner.scan_and_tokenize(&file_contents);
+            renderer.emit(&scanner.diagnostics);
+            if return_code != 0 {
+                exit(65);
+            }
+            let mut parsed_file = parser::Parser::new(scanner.tokens);
+            let statements = match parsed_file.parse() {
+                Ok(statements) => statements,
+                Err(errors) => {
+                    parser::report_errors(&renderer, &errors);
+                    exit(65)
+                }
+            };
+            let mut interpreter = Interpreter::new();
+            interpreter.set_script(filename, &file_contents);
+            let mut resolver = Resolver::new(&mut interpreter);
+            resolver.resolve(&statements);
+            renderer.emit(&resolver.diagnostics);
+            if resolver.had_error {
+                exit(65);
+            }
+            if let Err(error) = interpreter.execute(&statements) {
+                interpreter.report_error(&error);
+                exit(70);
+            }
+        }
         _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return;
+            eprintln!("Unknown command: {}", command);
         }
     }
 
 
 }
-
-/*
-fn tokenize(filename: &String) -> Vec<Token> {
-    writeln!(io::stderr(), "Logs from your program will appear here!").unwrap();
-
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-        writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
-        String::new()
-    });
-    if !file_contents.is_empty() {
-        let mut scanner = Scanner::new();
-        let return_code = scanner.scan_and_tokenize(&file_contents);
-        return scanner.tokens
-    }
-    Err(return_code)
-}*/
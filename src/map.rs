@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use crate::evaluator::{float_to_int, Value};
+
+/*
+Only values with structural equality can be keys. A float that is exactly equal to an int is
+stored as that int, since the two compare equal. Other floats are stored by their bit pattern
+so they can be hashed (-0.0 has already become the int 0 by then), and NaN is refused outright
+because it never equals itself and so could never be looked up again.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Number(u64),
+    String(String),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Result<MapKey, &'static str> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Bool(b) => Ok(MapKey::Bool(*b)),
+            Value::Int(n) => Ok(MapKey::Int(*n)),
+            Value::Number(n) if n.is_nan() => Err("Map key can't be NaN."),
+            Value::Number(n) => match float_to_int(*n) {
+                Some(int) => Ok(MapKey::Int(int)),
+                None => Ok(MapKey::Number(n.to_bits())),
+            },
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            _ => Err("Map keys must be strings, numbers, booleans or nil."),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Int(n) => Value::Int(*n),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+// Remembers insertion order so printing a map and iterating its keys are deterministic
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, Value)>,
+    index: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        self.index.get(key).map(|position| &self.entries[*position].1)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        match self.index.get(&key) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(MapKey, Value)> {
+        self.entries.iter()
+    }
+}
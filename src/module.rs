@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+use crate::environment::Environment;
+use crate::evaluator::{RunTimeError, Value};
+use crate::scanner::Token;
+
+/*
+The value an `import` binds. A module keeps its whole global scope alive, since its exported
+functions still close over it, but only the names it exported can be read from outside. Reads
+go through to that scope, so an exported variable reassigned inside the module is seen by
+every importer.
+ */
+pub struct LoxModule {
+    pub name: String,
+    pub globals: Rc<RefCell<Environment>>,
+    pub exports: HashSet<String>,
+}
+
+impl LoxModule {
+    pub fn get(&self, name: &Token) -> Result<Value, RunTimeError> {
+        if !self.exports.contains(&name.lexeme) {
+            return Err(RunTimeError::new(
+                name,
+                &format!("Module '{}' has no export '{}'.", self.name, name.lexeme),
+            ));
+        }
+        self.globals.borrow().get(name)
+    }
+}
+
+impl fmt::Debug for LoxModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::callable::{NativeFn, NativeFunction};
+use crate::environment::Environment;
+use crate::evaluator::{Interpreter, RunTimeError, Value};
+use crate::map::MapKey;
+use crate::scanner::Token;
+
+// Built-in functions available in every global scope. The arity is checked by the call site
+// before any of these run, so they can index `arguments` directly.
+pub fn define_natives(globals: &mut Environment) {
+    define(globals, "clock", 0, clock);
+    define(globals, "len", 1, len);
+    define(globals, "push", 2, push);
+    define(globals, "pop", 1, pop);
+    define(globals, "keys", 1, keys);
+    define(globals, "has", 2, has);
+}
+
+fn define(globals: &mut Environment, name: &str, arity: usize, function: NativeFn) {
+    globals.define(
+        name,
+        Value::NativeFunction(Rc::new(NativeFunction {
+            name: name.to_string(),
+            arity,
+            function,
+        })),
+    );
+}
+
+fn clock(
+    _interpreter: &mut Interpreter,
+    _arguments: &[Value],
+    _paren: &Token,
+) -> Result<Value, RunTimeError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+fn len(
+    _interpreter: &mut Interpreter,
+    arguments: &[Value],
+    paren: &Token,
+) -> Result<Value, RunTimeError> {
+    match &arguments[0] {
+        Value::List(list) => Ok(Value::Int(list.borrow().len() as i64)),
+        Value::Map(map) => Ok(Value::Int(map.borrow().len() as i64)),
+        Value::String(string) => Ok(Value::Int(string.chars().count() as i64)),
+        _ => Err(RunTimeError::new(paren, "len() expects a list, map or string.")),
+    }
+}
+
+fn push(
+    _interpreter: &mut Interpreter,
+    arguments: &[Value],
+    paren: &Token,
+) -> Result<Value, RunTimeError> {
+    match &arguments[0] {
+        Value::List(list) => {
+            list.borrow_mut().push(arguments[1].clone());
+            Ok(Value::Nil)
+        }
+        _ => Err(RunTimeError::new(paren, "push() expects a list.")),
+    }
+}
+
+fn pop(
+    _interpreter: &mut Interpreter,
+    arguments: &[Value],
+    paren: &Token,
+) -> Result<Value, RunTimeError> {
+    match &arguments[0] {
+        Value::List(list) => list
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| RunTimeError::new(paren, "Can't pop from an empty list.")),
+        _ => Err(RunTimeError::new(paren, "pop() expects a list.")),
+    }
+}
+
+// the keys of a map as a new list, in insertion order
+fn keys(
+    _interpreter: &mut Interpreter,
+    arguments: &[Value],
+    paren: &Token,
+) -> Result<Value, RunTimeError> {
+    match &arguments[0] {
+        Value::Map(map) => {
+            let keys = map.borrow().iter().map(|(key, _)| key.to_value()).collect();
+            Ok(Value::List(Rc::new(RefCell::new(keys))))
+        }
+        _ => Err(RunTimeError::new(paren, "keys() expects a map.")),
+    }
+}
+
+fn has(
+    _interpreter: &mut Interpreter,
+    arguments: &[Value],
+    paren: &Token,
+) -> Result<Value, RunTimeError> {
+    match &arguments[0] {
+        Value::Map(map) => {
+            let key = MapKey::from_value(&arguments[1])
+                .map_err(|message| RunTimeError::new(paren, message))?;
+            Ok(Value::Bool(map.borrow().contains_key(&key)))
+        }
+        _ => Err(RunTimeError::new(paren, "has() expects a map.")),
+    }
+}
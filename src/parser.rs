@@ -1,32 +1,186 @@
-use std::cmp::PartialEq;
-use std::panic::{self, UnwindSafe};
-use crate::scanner::{Literal, Token, TokenType};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::diagnostic::{Diagnostic, Renderer};
+use crate::scanner::{Literal, Span, Token, TokenType};
 
 /*
 Grammar:
-expression     → equality ;
+program        → declaration* EOF ;
+declaration    → classDecl
+               | enumDecl
+               | funDecl
+               | varDecl
+               | importDecl
+               | exportDecl
+               | statement ;
+classDecl      → "class" IDENTIFIER ( "<" IDENTIFIER )?
+                 "{" function* "}" ;
+enumDecl       → "enum" IDENTIFIER "{" ( variant ( "," variant )* ","? )? "}" ;
+variant        → IDENTIFIER ( "(" parameters? ")" )? ;
+funDecl        → "fun" function ;
+function       → IDENTIFIER functionBody ;
+functionBody   → "(" parameters? ")" block ;
+parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+importDecl     → "import" STRING "as" IDENTIFIER ";" ;
+exportDecl     → "export" ( classDecl | enumDecl | funDecl | varDecl ) ;
+statement      → exprStmt
+               | breakStmt
+               | continueStmt
+               | forStmt
+               | ifStmt
+               | printStmt
+               | returnStmt
+               | throwStmt
+               | tryStmt
+               | whileStmt
+               | block ;
+exprStmt       → expression ";" ;
+breakStmt      → "break" ";" ;
+continueStmt   → "continue" ";" ;
+forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+                 expression? ";"
+                 expression? ")" statement ;
+ifStmt         → "if" "(" expression ")" statement
+               ( "else" statement )? ;
+printStmt      → "print" expression ";" ;
+returnStmt     → "return" expression? ";" ;
+throwStmt      → "throw" expression ";" ;
+tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )?
+                 ( "finally" block )? ;
+whileStmt      → "while" "(" expression ")" statement ;
+block          → "{" declaration* "}" ;
+expression     → assignment ;
+assignment     → ( call "." )? IDENTIFIER "=" assignment
+               | call "[" expression "]" "=" assignment
+               | conditional ;
+conditional    → coalesce ( "?" expression ":" conditional )? ;
+coalesce       → logic_or ( "??" logic_or )* ;
+logic_or       → logic_and ( "or" logic_and )* ;
+logic_and      → equality ( "and" equality )* ;
 equality       → comparison ( ( "!=" | "==" ) comparison )* ;
-comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+comparison     → bit_or ( ( ">" | ">=" | "<" | "<=" ) bit_or )* ;
+bit_or         → bit_xor ( "|" bit_xor )* ;
+bit_xor        → bit_and ( "^" bit_and )* ;
+bit_and        → shift ( "&" shift )* ;
+shift          → term ( ( "<<" | ">>" ) term )* ;
 term           → factor ( ( "-" | "+" ) factor )* ;
-factor         → unary ( ( "/" | "*" ) unary )* ;
-unary          → ( "!" | "-" ) unary
-               | primary ;
+factor         → unary ( ( "/" | "*" | "%" | "~/" ) unary )* ;
+unary          → ( "!" | "-" | "~" ) unary
+               | power ;
+power          → call ( "**" unary )? ;
+call           → primary ( "(" arguments? ")" | "." IDENTIFIER
+                         | "[" expression "]" )* ;
+arguments      → expression ( "," expression )* ;
 primary        → NUMBER | STRING | "true" | "false" | "nil"
-               | "(" expression ")" ;
+               | ( INTERPOLATION expression )+ INTERPOLATION_END
+               | "fun" functionBody
+               | "match" expression "{" arm ( "," arm )* ","? "}"
+               | "(" parameters? ")" "=>" expression
+               | "(" expression ")" | IDENTIFIER | "this"
+               | "super" "." IDENTIFIER
+               | "[" ( expression ( "," expression )* ","? )? "]"
+               | "{" ( entry ( "," entry )* ","? )? "}" ;
+entry          → expression ":" expression ;
+arm            → pattern ( "if" expression )? "=>" expression ;
+pattern        → "_" | IDENTIFIER
+               | NUMBER | "-" NUMBER | STRING | "true" | "false" | "nil"
+               | "[" ( pattern ( "," pattern )* )? ( "," ".." IDENTIFIER? )? "]"
+               | "{" ( literal ":" pattern ( "," literal ":" pattern )* )? "}"
+               | path "{" ( field ( "," field )* )? "}"
+               | path "." IDENTIFIER ( "(" ( pattern ( "," pattern )* )? ")" )? ;
+path           → IDENTIFIER ( "." IDENTIFIER )* ;
+field          → IDENTIFIER ( ":" pattern )? ;
+
+A "{" at the start of a statement always opens a block; map literals are only parsed where an
+expression is expected, so wrap one in parentheses to use it as an expression statement. In the
+same way "fun" followed by a name is a declaration, and followed by "(" an anonymous function.
+"as" is only special inside an import, it stays usable as an ordinary name everywhere else.
  */
 
-#[derive(Debug)]
-pub struct ParseError;
+// What went wrong, for tools that want to react to particular mistakes rather than print them
+#[derive(Debug, Clone)]
+pub enum ParseErrorKind {
+    ExpectedExpression,
+    ExpectedPattern,
+    ExpectedDeclaration,
+    // some other specific token was required, `expected` on the error says which
+    ExpectedToken,
+    UnclosedParen { opened_at: Token },
+    UnclosedBracket { opened_at: Token },
+    UnclosedBrace { opened_at: Token },
+    InvalidAssignmentTarget { target: Option<Span> },
+    LoopControlOutsideLoop,
+    MissingCatchOrFinally,
+    IntegerOverflow,
+}
+
+// Passed around boxed inside the parser, so that the Ok path of every parsing function doesn't
+// have to be as large as an error
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    // where the parser was when it gave up, the Eof token if it ran out of input
+    pub token: Token,
+    pub message: String,
+    // the token types that would have been accepted instead of `token`, empty when the
+    // problem isn't a missing token
+    pub expected: Vec<TokenType>,
+}
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Parse error")
+        if self.token.token_type == TokenType::Eof {
+            write!(f, "[line {}] Error at end: {}", self.token.line, self.message)
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.token.line, self.token.lexeme, self.message
+            )
+        }
     }
 }
 
 impl std::error::Error for ParseError {
 }
 
+impl ParseError {
+    // the error along with the bracket that was left open, or the tokens that would have fit
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::error(self.to_string(), self.token.span);
+        match &self.kind {
+            ParseErrorKind::UnclosedParen { opened_at } => {
+                diagnostic.with_label(opened_at.span, "opening paren was here")
+            }
+            ParseErrorKind::UnclosedBracket { opened_at } => {
+                diagnostic.with_label(opened_at.span, "opening bracket was here")
+            }
+            ParseErrorKind::UnclosedBrace { opened_at } => {
+                diagnostic.with_label(opened_at.span, "opening brace was here")
+            }
+            ParseErrorKind::InvalidAssignmentTarget {
+                target: Some(target),
+            } => diagnostic.with_label(*target, "can't assign to this"),
+            // a single alternative is already spelled out by the message
+            _ if self.expected.len() > 1 && self.expected.len() <= 4 => {
+                let expected: Vec<String> = self
+                    .expected
+                    .iter()
+                    .map(|token_type| token_type.to_string())
+                    .collect();
+                diagnostic.with_help(format!("expected one of {}", expected.join(", ")))
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+pub fn report_errors(renderer: &Renderer, errors: &[ParseError]) {
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(ParseError::diagnostic).collect();
+    renderer.emit(&diagnostics);
+}
+
 #[derive(Debug, Clone)]
 pub enum Expr {
     Binary {
@@ -37,6 +191,9 @@ pub enum Expr {
 
         right: Box<Expr>,
 
+        // from the start of `left` to the end of `right`
+        span: Span,
+
     },
 
     Grouping {
@@ -57,35 +214,564 @@ pub enum Expr {
 
         right: Box<Expr>,
 
+        // the operator through the end of its operand
+        span: Span,
+
+    },
+
+    Variable {
+
+        id: usize,
+
+        name: Token,
+
     },
+
+    Logical {
+
+        left: Box<Expr>,
+
+        operator: Token,
+
+        right: Box<Expr>,
+
+        // from the start of `left` to the end of `right`
+        span: Span,
+
+    },
+
+    Conditional {
+
+        condition: Box<Expr>,
+
+        then_branch: Box<Expr>,
+
+        else_branch: Box<Expr>,
+
+        // the condition through the end of the else branch
+        span: Span,
+
+    },
+
+    Assign {
+
+        id: usize,
+
+        name: Token,
+
+        value: Box<Expr>,
+
+        // the name through the end of the value
+        span: Span,
+
+    },
+
+    Call {
+
+        callee: Box<Expr>,
+
+        paren: Token,
+
+        arguments: Vec<Expr>,
+
+        // the callee through the closing paren
+        span: Span,
+
+    },
+
+    Get {
+
+        object: Box<Expr>,
+
+        name: Token,
+
+        // the object through the property name
+        span: Span,
+
+    },
+
+    Set {
+
+        object: Box<Expr>,
+
+        name: Token,
+
+        value: Box<Expr>,
+
+        // the object through the end of the value
+        span: Span,
+
+    },
+
+    This {
+
+        id: usize,
+
+        keyword: Token,
+
+    },
+
+    Super {
+
+        id: usize,
+
+        keyword: Token,
+
+        method: Token,
+
+    },
+
+    List {
+
+        elements: Vec<Expr>,
+
+    },
+
+    Index {
+
+        object: Box<Expr>,
+
+        bracket: Token,
+
+        index: Box<Expr>,
+
+        // the object through the closing bracket
+        span: Span,
+
+    },
+
+    IndexSet {
+
+        object: Box<Expr>,
+
+        bracket: Token,
+
+        index: Box<Expr>,
+
+        value: Box<Expr>,
+
+        // the object through the end of the value
+        span: Span,
+
+    },
+
+    Map {
+
+        brace: Token,
+
+        entries: Vec<(Expr, Expr)>,
+
+    },
+
+    // only produced by lowering string interpolation, turns any value into its printed form
+    Stringify {
+
+        expression: Box<Expr>,
+
+    },
+
+    // both `fun (a) { ... }` and `(a) => ...`, the arrow form's body is a single return
+    Lambda {
+
+        declaration: Rc<FunctionDecl>,
+
+    },
+
+    Match {
+
+        keyword: Token,
+
+        subject: Box<Expr>,
+
+        arms: Vec<MatchArm>,
+
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // `_`, matches anything without binding it
+    Wildcard,
+
+    Binding {
+
+        name: Token,
+
+    },
+
+    Literal {
+
+        value: Literal,
+
+    },
+
+    // without a rest pattern the list must have exactly as many elements
+    List {
+
+        elements: Vec<Pattern>,
+
+        rest: Option<Box<Pattern>>,
+
+    },
+
+    // matches any map that has at least these keys
+    Map {
+
+        entries: Vec<(Literal, Pattern)>,
+
+    },
+
+    // `Point { x, y: 0 }`, an instance of the class or one of its subclasses
+    Instance {
+
+        class: Expr,
+
+        fields: Vec<(Token, Pattern)>,
+
+    },
+
+    // `Shape.Circle(r)`; without the parens the variant matches whatever its fields hold
+    Variant {
+
+        enumeration: Expr,
+
+        variant: Token,
+
+        fields: Option<Vec<Pattern>>,
+
+    },
+}
+
+impl Pattern {
+    // visits every name the pattern binds, in order
+    pub fn bindings<'a>(&'a self, found: &mut Vec<&'a Token>) {
+        match self {
+            Pattern::Binding { name } => found.push(name),
+            Pattern::List { elements, rest } => {
+                for element in elements {
+                    element.bindings(found);
+                }
+                if let Some(rest) = rest {
+                    rest.bindings(found);
+                }
+            }
+            Pattern::Map { entries } => {
+                for (_, pattern) in entries {
+                    pattern.bindings(found);
+                }
+            }
+            Pattern::Instance { fields, .. } => {
+                for (_, pattern) in fields {
+                    pattern.bindings(found);
+                }
+            }
+            Pattern::Variant { fields, .. } => {
+                for pattern in fields.iter().flatten() {
+                    pattern.bindings(found);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal { .. } => {}
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding { name } => write!(f, "{}", name.lexeme),
+            Pattern::Literal { value } => write!(f, "{}", Expr::new_literal(value.clone())),
+            Pattern::List { elements, rest } => {
+                write!(f, "[")?;
+                for (position, element) in elements.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                if let Some(rest) = rest {
+                    write!(f, " ..{}", rest)?;
+                }
+                write!(f, "]")
+            }
+            Pattern::Map { entries } => {
+                write!(f, "{{")?;
+                for (position, (key, pattern)) in entries.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}: {}", Expr::new_literal(key.clone()), pattern)?;
+                }
+                write!(f, "}}")
+            }
+            Pattern::Instance { class, fields } => {
+                write!(f, "{}{{", class)?;
+                for (position, (name, pattern)) in fields.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}: {}", name.lexeme, pattern)?;
+                }
+                write!(f, "}}")
+            }
+            Pattern::Variant {
+                enumeration,
+                variant,
+                fields,
+            } => {
+                write!(f, "{}.{}", enumeration, variant.lexeme)?;
+                if let Some(fields) = fields {
+                    write!(f, "(")?;
+                    for (position, field) in fields.iter().enumerate() {
+                        if position > 0 {
+                            write!(f, " ")?;
+                        }
+                        write!(f, "{}", field)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-pub enum Object {
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expression {
+
+        expression: Expr,
+
+    },
+
+    Print {
+
+        expression: Expr,
+
+    },
+
+    Var {
+
+        name: Token,
+
+        initializer: Option<Expr>,
 
-    Number(f32),
+    },
+
+    Block {
+
+        statements: Vec<Stmt>,
+
+    },
+
+    If {
+
+        condition: Expr,
+
+        then_branch: Box<Stmt>,
+
+        else_branch: Option<Box<Stmt>>,
+
+    },
+
+    While {
+
+        condition: Expr,
+
+        body: Box<Stmt>,
+
+        // only set for desugared `for` loops, runs after the body even when it `continue`s
+        increment: Option<Expr>,
+
+    },
+
+    Break,
+
+    Continue,
+
+    Function {
+
+        declaration: Rc<FunctionDecl>,
+
+    },
+
+    Class {
 
-    String(String),
+        name: Token,
 
-    Boolean(bool),
+        superclass: Option<Expr>,
 
-    Nil,
+        methods: Vec<Rc<FunctionDecl>>,
+
+    },
+
+    Return {
+
+        keyword: Token,
+
+        value: Option<Expr>,
+
+    },
+
+    Enum {
+
+        name: Token,
+
+        // each variant's name and the names of its fields
+        variants: Vec<(Token, Vec<Token>)>,
+
+    },
+
+    Throw {
+
+        keyword: Token,
+
+        value: Expr,
+
+    },
+
+    Import {
+
+        keyword: Token,
+
+        path: Token,
+
+        name: Token,
+
+    },
 
+    // always wraps a class, function or variable declaration
+    Export {
+
+        declaration: Box<Stmt>,
+
+    },
+
+    // at least one of `handler` and `finally` is always present
+    Try {
+
+        body: Vec<Stmt>,
+
+        // the name the caught value is bound to, and the catch block
+        handler: Option<(Token, Vec<Stmt>)>,
+
+        finally: Option<Vec<Stmt>>,
+
+    },
+}
+
+impl Stmt {
+    // the name a declaration binds, for the declarations that can be exported
+    pub fn declared_name(&self) -> Option<&Token> {
+        match self {
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::Enum { name, .. } => {
+                Some(name)
+            }
+            Stmt::Function { declaration } => Some(&declaration.name),
+            _ => None,
+        }
+    }
+}
+
+// Shared between the AST and every function value created from it, so calling a function
+// never has to clone its body
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+// Every variable reference gets a unique id so the resolver can record how far away its binding
+// lives. Ids are global rather than per-parser so that several parsed files can share one
+// interpreter without clashing.
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn next_expr_id() -> usize {
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// everything `primary` can start with, reported as the alternatives when an expression is missing
+const EXPRESSION_START: [TokenType; 17] = [
+    TokenType::Number,
+    TokenType::String,
+    TokenType::Interpolation,
+    TokenType::Identifier,
+    TokenType::True,
+    TokenType::False,
+    TokenType::Nil,
+    TokenType::This,
+    TokenType::Super,
+    TokenType::Fun,
+    TokenType::Match,
+    TokenType::LeftParen,
+    TokenType::LeftBracket,
+    TokenType::LeftBrace,
+    TokenType::Minus,
+    TokenType::Bang,
+    TokenType::Tilde,
+];
+
+// lambdas have no name of their own, this one keeps the position of the token that introduced them
+fn anonymous_name(token: &Token) -> Token {
+    Token {
+        token_type: TokenType::Identifier,
+        lexeme: "anonymous".to_string(),
+        literal: None,
+        line: token.line,
+        span: token.span,
+        file: token.file.clone(),
+    }
 }
 
 impl Expr {
-    fn new_binary(left: Expr, operator: Token, right: Expr) -> Self {
+    fn new_binary(left: Expr, operator: Token, right: Expr, span: Span) -> Self {
         Expr::Binary {
             left: Box::new(left),
             operator,
             right: Box::new(right),
+            span,
+        }
+    }
+
+    fn new_logical(left: Expr, operator: Token, right: Expr, span: Span) -> Self {
+        Expr::Logical {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span,
         }
     }
 
-    fn new_unary(operator: Token, right: Expr) -> Self {
+    fn new_unary(operator: Token, right: Expr, span: Span) -> Self {
         Expr::Unary {
             operator,
             right: Box::new(right),
+            span,
+        }
+    }
+
+    // the source the expression covers, for the nodes that keep track of it
+    fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Binary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Conditional { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Get { span, .. }
+            | Expr::Set { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::IndexSet { span, .. } => Some(*span),
+            _ => None,
         }
     }
 
@@ -102,13 +788,14 @@ impl Expr {
     }
 }
 
-impl<'a> std::fmt::Display for Expr {
+impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => {
                 write!(f, "({} {} {})", operator.lexeme, left, right)
             }
@@ -125,10 +812,120 @@ impl<'a> std::fmt::Display for Expr {
                 })
             }
 
-            Expr::Unary { operator, right } => {
+            Expr::Unary { operator, right, .. } => {
                 write!(f, "({} {})", operator.lexeme, right)
             }
 
+            Expr::Variable { name, .. } => {
+                write!(f, "{}", name.lexeme)
+            }
+
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                write!(f, "({} {} {})", operator.lexeme, left, right)
+            }
+
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                write!(f, "(?: {} {} {})", condition, then_branch, else_branch)
+            }
+
+            Expr::Assign { name, value, .. } => {
+                write!(f, "(= {} {})", name.lexeme, value)
+            }
+
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+
+            Expr::Get { object, name, .. } => {
+                write!(f, "(get {} {})", object, name.lexeme)
+            }
+
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                write!(f, "(set {} {} {})", object, name.lexeme, value)
+            }
+
+            Expr::This { .. } => {
+                write!(f, "this")
+            }
+
+            Expr::Super { method, .. } => {
+                write!(f, "(super {})", method.lexeme)
+            }
+
+            Expr::List { elements } => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+
+            Expr::Index { object, index, .. } => {
+                write!(f, "(index {} {})", object, index)
+            }
+
+            Expr::Stringify { expression } => {
+                write!(f, "(str {})", expression)
+            }
+
+            Expr::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, value) in entries {
+                    write!(f, " ({} {})", key, value)?;
+                }
+                write!(f, ")")
+            }
+
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                write!(f, "(index= {} {} {})", object, index, value)
+            }
+
+            Expr::Lambda { declaration } => {
+                write!(f, "(fun")?;
+                for param in &declaration.params {
+                    write!(f, " {}", param.lexeme)?;
+                }
+                write!(f, ")")
+            }
+
+            Expr::Match { subject, arms, .. } => {
+                write!(f, "(match {}", subject)?;
+                for arm in arms {
+                    write!(f, " ({}", arm.pattern)?;
+                    if let Some(guard) = &arm.guard {
+                        write!(f, " if {}", guard)?;
+                    }
+                    write!(f, " {})", arm.body)?;
+                }
+                write!(f, ")")
+            }
+
         }
     }
 }
@@ -136,6 +933,10 @@ impl<'a> std::fmt::Display for Expr {
 pub struct Parser {
     pub(crate) tokens: Vec<Token>,
     current: usize,
+    // how many loops enclose the current statement, `break` and `continue` need at least one
+    loop_depth: usize,
+    // every error found so far; parsing carries on after most of them
+    errors: Vec<ParseError>,
 }
 
 impl Parser { // TODO: Return exit code on null error :) test 3 expected exit code 65, got 0
@@ -143,76 +944,345 @@ impl Parser { // TODO: Return exit code on null error :) test 3 expected exit co
         Self {
             tokens: tokens_vector,
             current: 0,
+            loop_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn expression(&mut self) -> Result<Expr, Box<ParseError>> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let expr = self.conditional()?;
+
+        if self.match_tokens(&[TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+            let span = self.span_from(start);
+
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        id: next_expr_id(),
+                        name,
+                        value: Box::new(value),
+                        span,
+                    });
+                }
+                Expr::Get { object, name, .. } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                        span,
+                    });
+                }
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                    ..
+                } => {
+                    return Ok(Expr::IndexSet {
+                        object,
+                        bracket,
+                        index,
+                        value: Box::new(value),
+                        span,
+                    });
+                }
+                _ => {}
+            }
+
+            // the parser is still in a sensible state, so record it without unwinding
+            let error = self.error(
+                &equals,
+                ParseErrorKind::InvalidAssignmentTarget {
+                    target: expr.span(),
+                },
+                "Invalid assignment target.",
+            );
+            self.errors.push(error);
+            return Ok(value);
+        }
+
+        Ok(expr)
+    }
+
+    fn conditional(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let condition = self.coalesce()?;
+
+        if self.match_tokens(&[TokenType::Question]) {
+            let then_branch = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after then branch of conditional expression.")?;
+            let else_branch = self.conditional()?;
+            return Ok(Expr::Conditional {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+                span: self.span_from(start),
+            });
+        }
+
+        Ok(condition)
+    }
+
+    fn coalesce(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.or()?;
+
+        while self.match_tokens(&[TokenType::QuestionQuestion]) {
+            let operator = self.previous().clone();
+            let right = self.or()?;
+            expr = Expr::new_logical(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.and()?;
+
+        while self.match_tokens(&[TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::new_logical(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.equality()?;
+
+        while self.match_tokens(&[TokenType::And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::new_logical(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.comparison()?;
+
+        while self.match_tokens(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+            let operator = self.previous().clone();
+            let right = self.comparison()?;
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
         }
+
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.bit_or()?;
+        while self.match_tokens(&[
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ]) {
+            let operator = self.previous().clone();
+            let right = self.bit_or()?;
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
+    }
+
+    // the bitwise operators bind tighter than comparison, unlike C, so that
+    // `flags & MASK == 0` means what it looks like it means
+    fn bit_or(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.bit_xor()?;
+
+        while self.match_tokens(&[TokenType::Pipe]) {
+            let operator = self.previous().clone();
+            let right = self.bit_xor()?;
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
     }
 
-    pub fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.equality()
+    fn bit_xor(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.bit_and()?;
+
+        while self.match_tokens(&[TokenType::Caret]) {
+            let operator = self.previous().clone();
+            let right = self.bit_and()?;
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
+        }
+
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.comparison()?;
+    fn bit_and(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.shift()?;
 
-        while self.match_tokens(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+        while self.match_tokens(&[TokenType::Ampersand]) {
             let operator = self.previous().clone();
-            let right = self.comparison()?;
-            expr = Expr::new_binary(expr, operator, right);
+            let right = self.shift()?;
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
         }
 
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, ParseError> {
+    fn shift(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
         let mut expr = self.term()?;
-        while self.match_tokens(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
+
+        while self.match_tokens(&[TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.previous().clone();
             let right = self.term()?;
-            expr = Expr::new_binary(expr, operator, right);
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
         }
 
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, ParseError> {
+    fn term(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
         let mut expr = self.factor()?;
 
         while self.match_tokens(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous().clone();
             let right = self.factor()?;
-            expr = Expr::new_binary(expr, operator, right)
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start))
         }
 
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, ParseError> {
+    fn factor(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
         let mut expr = self.unary()?;
 
-        while self.match_tokens(&[TokenType::Slash, TokenType::Star]) {
+        while self.match_tokens(&[
+            TokenType::Slash,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::TildeSlash,
+        ]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
-            expr = Expr::new_binary(expr, operator, right);
+            expr = Expr::new_binary(expr, operator, right, self.span_from(start));
         }
 
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.match_tokens(&[TokenType::Minus, TokenType::Bang]) {
+    fn unary(&mut self) -> Result<Expr, Box<ParseError>> {
+        // the digits of -9223372036854775808 don't fit an i64 on their own, so the minus is
+        // folded into the literal rather than applied to it
+        let overflowing = self.tokens.get(self.current + 1).is_some_and(|token| {
+            token.token_type == TokenType::Number
+                && !token.lexeme.contains('.')
+                && token.lexeme.parse::<i64>().is_err()
+        });
+        if overflowing && self.match_tokens(&[TokenType::Minus]) {
+            self.advance();
+            return Ok(Expr::new_literal(self.number_literal(true)?));
+        }
+
+        if self.match_tokens(&[TokenType::Minus, TokenType::Bang, TokenType::Tilde]) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+            let span = self.span_from(operator.span);
+            return Ok(Expr::new_unary(operator, right, span));
+        }
+
+        self.power()
+    }
+
+    // binds tighter than a unary minus on its left, so -2 ** 2 is -(2 ** 2), and recurses
+    // through unary on its right, which makes it right-associative and allows 2 ** -1
+    fn power(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let expr = self.call()?;
+
+        if self.match_tokens(&[TokenType::StarStar]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
-            return Ok(Expr::new_unary(operator, right));
+            return Ok(Expr::new_binary(expr, operator, right, self.span_from(start)));
+        }
+
+        Ok(expr)
+    }
+
+    fn call(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.peek().span;
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_tokens(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr, start)?;
+            } else if self.match_tokens(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "Expect property name after '.'.")?
+                    .clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                    span: self.span_from(start),
+                };
+            } else if self.match_tokens(&[TokenType::LeftBracket]) {
+                let opened_at = self.previous().clone();
+                let index = self.expression()?;
+                let bracket = self
+                    .consume_closing(TokenType::RightBracket, &opened_at, "Expect ']' after index.")?
+                    .clone();
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                    span: self.span_from(start),
+                };
+            } else {
+                break;
+            }
         }
 
-        self.primary()
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr, start: Span) -> Result<Expr, Box<ParseError>> {
+        let opened_at = self.previous().clone();
+        let mut arguments = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after arguments.")?
+            .clone();
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+            span: self.span_from(start),
+        })
     }
 
-    pub(crate) fn primary(&mut self) -> Result<Expr, ParseError> {
+    pub(crate) fn primary(&mut self) -> Result<Expr, Box<ParseError>> {
         if self.match_tokens(&[TokenType::True]) {
             return Ok(Expr::new_literal(Literal::Bool(true)));
         }
@@ -225,25 +1295,531 @@ impl Parser { // TODO: Return exit code on null error :) test 3 expected exit co
             return Ok(Expr::new_literal(Literal::Nil));
         }
 
-        if self.match_tokens(&[TokenType::Number, TokenType::String]) {
+        if self.match_tokens(&[TokenType::Number]) {
+            return Ok(Expr::new_literal(self.number_literal(false)?));
+        }
+
+        if self.match_tokens(&[TokenType::String]) {
             let token = self.previous().clone();
             return Ok(Expr::new_literal(token.literal.clone().unwrap()));
         }
 
+        if self.match_tokens(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.match_tokens(&[TokenType::Fun]) {
+            let name = anonymous_name(self.previous());
+            return Ok(Expr::Lambda {
+                declaration: self.function_body(name, "function")?,
+            });
+        }
+
+        if self.match_tokens(&[TokenType::Match]) {
+            return self.match_expression();
+        }
+
+        if self.check(&TokenType::LeftParen) && self.is_arrow_parameters() {
+            return self.arrow_function();
+        }
+
         if self.match_tokens(&[TokenType::LeftParen]) {
+            let opened_at = self.previous().clone();
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            self.consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after expression.")?;
             return Ok(Expr::new_grouping(expr));
         }
 
-        Err(ParseError)
+        if self.match_tokens(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expect superclass method name.")?
+                .clone();
+            return Ok(Expr::Super {
+                id: next_expr_id(),
+                keyword,
+                method,
+            });
+        }
+
+        if self.match_tokens(&[TokenType::This]) {
+            return Ok(Expr::This {
+                id: next_expr_id(),
+                keyword: self.previous().clone(),
+            });
+        }
+
+        if self.match_tokens(&[TokenType::LeftBracket]) {
+            let opened_at = self.previous().clone();
+            let mut elements = Vec::new();
+            while !self.check(&TokenType::RightBracket) {
+                elements.push(self.expression()?);
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume_closing(
+                TokenType::RightBracket,
+                &opened_at,
+                "Expect ']' after list elements.",
+            )?;
+            return Ok(Expr::List { elements });
+        }
+
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            let opened_at = self.previous().clone();
+            let mut entries = Vec::new();
+            while !self.check(&TokenType::RightBrace) {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                let value = self.expression()?;
+                entries.push((key, value));
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            let brace = self
+                .consume_closing(TokenType::RightBrace, &opened_at, "Expect '}' after map entries.")?
+                .clone();
+            return Ok(Expr::Map { brace, entries });
+        }
+
+        if self.match_tokens(&[TokenType::Identifier]) {
+            return Ok(Expr::Variable {
+                id: next_expr_id(),
+                name: self.previous().clone(),
+            });
+        }
+
+        let error = self.error_at_current(ParseErrorKind::ExpectedExpression, "Expect expression.");
+        Err(Box::new(ParseError {
+            expected: EXPRESSION_START.to_vec(),
+            ..error
+        }))
+    }
+
+    fn match_expression(&mut self) -> Result<Expr, Box<ParseError>> {
+        let keyword = self.previous().clone();
+        let subject = self.expression()?;
+        let opened_at = self.consume(TokenType::LeftBrace, "Expect '{' after match subject.")?.clone();
+
+        let mut arms = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let pattern = self.pattern()?;
+            let guard = if self.match_tokens(&[TokenType::If]) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            self.consume(TokenType::Arrow, "Expect '=>' after pattern.")?;
+            let body = self.expression()?;
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume_closing(TokenType::RightBrace, &opened_at, "Expect '}' after match arms.")?;
+
+        Ok(Expr::Match {
+            keyword,
+            subject: Box::new(subject),
+            arms,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, Box<ParseError>> {
+        if self.match_tokens(&[TokenType::LeftBracket]) {
+            return self.list_pattern();
+        }
+
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            let opened_at = self.previous().clone();
+            let mut entries = Vec::new();
+            while !self.check(&TokenType::RightBrace) {
+                let key = self.literal_pattern()?;
+                self.consume(TokenType::Colon, "Expect ':' after map pattern key.")?;
+                entries.push((key, self.pattern()?));
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume_closing(
+                TokenType::RightBrace,
+                &opened_at,
+                "Expect '}' after map pattern.",
+            )?;
+            return Ok(Pattern::Map { entries });
+        }
+
+        if self.match_tokens(&[TokenType::Identifier]) {
+            let name = self.previous().clone();
+            if !self.check(&TokenType::Dot) && !self.check(&TokenType::LeftBrace) {
+                if name.lexeme == "_" {
+                    return Ok(Pattern::Wildcard);
+                }
+                return Ok(Pattern::Binding { name });
+            }
+
+            // `m.Shape.Circle(r)` or `m.Point { x }`: every name but a variant's is looked up
+            // like a property, so classes and enums can be matched from another module
+            let start = name.span;
+            let mut path = Expr::Variable {
+                id: next_expr_id(),
+                name,
+            };
+            let mut last = None;
+            while self.match_tokens(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "Expect name after '.' in pattern.")?
+                    .clone();
+                if let Some(previous) = last.replace(name) {
+                    path = Expr::Get {
+                        object: Box::new(path),
+                        span: start.to(previous.span),
+                        name: previous,
+                    };
+                }
+            }
+            if self.match_tokens(&[TokenType::LeftBrace]) {
+                if let Some(name) = last {
+                    path = Expr::Get {
+                        object: Box::new(path),
+                        span: start.to(name.span),
+                        name,
+                    };
+                }
+                return self.instance_pattern(path);
+            }
+            // there was at least one '.' to get here, so the last name is the variant
+            return match last {
+                Some(variant) => self.variant_pattern(path, variant),
+                None => Err(Box::new(self.error_at_current(
+                    ParseErrorKind::ExpectedPattern,
+                    "Expect pattern.",
+                ))),
+            };
+        }
+
+        Ok(Pattern::Literal {
+            value: self.literal_pattern()?,
+        })
+    }
+
+    fn literal_pattern(&mut self) -> Result<Literal, Box<ParseError>> {
+        if self.match_tokens(&[TokenType::True]) {
+            return Ok(Literal::Bool(true));
+        }
+        if self.match_tokens(&[TokenType::False]) {
+            return Ok(Literal::Bool(false));
+        }
+        if self.match_tokens(&[TokenType::Nil]) {
+            return Ok(Literal::Nil);
+        }
+        if self.match_tokens(&[TokenType::Number]) {
+            return self.number_literal(false);
+        }
+        if self.match_tokens(&[TokenType::String]) {
+            return Ok(self.previous().literal.clone().unwrap());
+        }
+        // negative numbers are a unary minus in expressions, patterns fold it in
+        if self.match_tokens(&[TokenType::Minus]) {
+            self.consume(TokenType::Number, "Expect number after '-' in pattern.")?;
+            return self.number_literal(true);
+        }
+        Err(Box::new(self.error_at_current(ParseErrorKind::ExpectedPattern, "Expect pattern.")))
+    }
+
+    // the value of the number token just consumed, negated if a minus was folded into it
+    fn number_literal(&self, negate: bool) -> Result<Literal, Box<ParseError>> {
+        let token = self.previous();
+        // a float keeps its value, an int is read back from its digits, since the one the
+        // scanner lets through past i64::MAX only fits once the minus is applied
+        let literal = match &token.literal {
+            Some(Literal::Number(value)) if token.lexeme.contains('.') => {
+                Some(Literal::Number(if negate { -value } else { *value }))
+            }
+            _ => {
+                let digits = if negate {
+                    format!("-{}", token.lexeme)
+                } else {
+                    token.lexeme.clone()
+                };
+                digits.parse::<i64>().ok().map(Literal::Int)
+            }
+        };
+        literal.ok_or_else(|| {
+            Box::new(self.error(
+                token,
+                ParseErrorKind::IntegerOverflow,
+                "Integer literal too large.",
+            ))
+        })
+    }
+
+    fn list_pattern(&mut self) -> Result<Pattern, Box<ParseError>> {
+        let opened_at = self.previous().clone();
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while !self.check(&TokenType::RightBracket) {
+            // `..name` collects whatever is left, `..` on its own just allows extra elements
+            if self.match_tokens(&[TokenType::Dot]) {
+                self.consume(TokenType::Dot, "Expect '..' before rest pattern.")?;
+                rest = Some(Box::new(if self.match_tokens(&[TokenType::Identifier]) {
+                    Pattern::Binding {
+                        name: self.previous().clone(),
+                    }
+                } else {
+                    Pattern::Wildcard
+                }));
+                break;
+            }
+            elements.push(self.pattern()?);
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume_closing(
+            TokenType::RightBracket,
+            &opened_at,
+            "Expect ']' after list pattern.",
+        )?;
+        Ok(Pattern::List { elements, rest })
+    }
+
+    fn variant_pattern(
+        &mut self,
+        enumeration: Expr,
+        variant: Token,
+    ) -> Result<Pattern, Box<ParseError>> {
+        let mut fields = None;
+        if self.match_tokens(&[TokenType::LeftParen]) {
+            let opened_at = self.previous().clone();
+            let mut patterns = Vec::new();
+            while !self.check(&TokenType::RightParen) {
+                patterns.push(self.pattern()?);
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume_closing(
+                TokenType::RightParen,
+                &opened_at,
+                "Expect ')' after variant fields.",
+            )?;
+            fields = Some(patterns);
+        }
+
+        Ok(Pattern::Variant {
+            enumeration,
+            variant,
+            fields,
+        })
+    }
+
+    fn instance_pattern(&mut self, class: Expr) -> Result<Pattern, Box<ParseError>> {
+        let opened_at = self.previous().clone();
+        let mut fields = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let name = self
+                .consume(TokenType::Identifier, "Expect field name in pattern.")?
+                .clone();
+            // a bare field name binds the field to a variable of the same name
+            let pattern = if self.match_tokens(&[TokenType::Colon]) {
+                self.pattern()?
+            } else {
+                Pattern::Binding { name: name.clone() }
+            };
+            fields.push((name, pattern));
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume_closing(
+            TokenType::RightBrace,
+            &opened_at,
+            "Expect '}' after instance pattern.",
+        )?;
+        Ok(Pattern::Instance {
+            class,
+            fields,
+        })
+    }
+
+    // only a parameter list can be directly followed by `=>`, so scan ahead for the closing
+    // paren before committing to either a grouping or an arrow function
+    fn is_arrow_parameters(&self) -> bool {
+        let mut position = self.current + 1;
+        if self.token_type_at(position) != Some(&TokenType::RightParen) {
+            loop {
+                if self.token_type_at(position) != Some(&TokenType::Identifier) {
+                    return false;
+                }
+                position += 1;
+                if self.token_type_at(position) != Some(&TokenType::Comma) {
+                    break;
+                }
+                position += 1;
+            }
+        }
+        self.token_type_at(position) == Some(&TokenType::RightParen)
+            && self.token_type_at(position + 1) == Some(&TokenType::Arrow)
+    }
+
+    fn arrow_function(&mut self) -> Result<Expr, Box<ParseError>> {
+        self.consume(TokenType::LeftParen, "Expect '(' before parameters.")?;
+        let params = self.parameters()?;
+        let arrow = self.consume(TokenType::Arrow, "Expect '=>' after parameters.")?.clone();
+
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let value = self.expression();
+        self.loop_depth = enclosing_loop_depth;
+
+        let body = vec![Stmt::Return {
+            keyword: arrow.clone(),
+            value: Some(value?),
+        }];
+        Ok(Expr::Lambda {
+            declaration: Rc::new(FunctionDecl {
+                name: anonymous_name(&arrow),
+                params,
+                body,
+            }),
+        })
+    }
+
+    // "a${b}c${d}e" arrives as INTERPOLATION(a) b INTERPOLATION(c) d INTERPOLATION_END(e) and is
+    // lowered to "a" + str(b) + "c" + str(d) + "e"
+    fn interpolation(&mut self) -> Result<Expr, Box<ParseError>> {
+        let start = self.previous().clone();
+        let plus = Token {
+            token_type: TokenType::Plus,
+            lexeme: "+".to_string(),
+            literal: None,
+            line: start.line,
+            span: start.span,
+            file: start.file.clone(),
+        };
+
+        let mut expr = Expr::new_literal(start.literal.unwrap());
+        loop {
+            let embedded = Expr::Stringify {
+                expression: Box::new(self.expression()?),
+            };
+            expr = Expr::new_binary(expr, plus.clone(), embedded, self.span_from(start.span));
+
+            if self.match_tokens(&[TokenType::Interpolation]) {
+                let part = self.previous().literal.clone().unwrap();
+                expr = Expr::new_binary(
+                    expr,
+                    plus.clone(),
+                    Expr::new_literal(part),
+                    self.span_from(start.span),
+                );
+                continue;
+            }
+
+            let end = self.consume(
+                TokenType::InterpolationEnd,
+                "Expect '}' after interpolated expression.",
+            )?;
+            let part = end.literal.clone().unwrap();
+            let span = self.span_from(start.span);
+            return Ok(Expr::new_binary(expr, plus, Expr::new_literal(part), span));
+        }
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, Box<ParseError>> {
+        if self.check(&token_type) {
+            return Ok(self.advance());
+        }
+        let error = self.error_at_current(ParseErrorKind::ExpectedToken, message);
+        Err(Box::new(ParseError {
+            expected: vec![token_type],
+            ..error
+        }))
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
+    // like `consume`, but a miss is reported as the opening delimiter never being closed
+    fn consume_closing(
+        &mut self,
+        token_type: TokenType,
+        opened_at: &Token,
+        message: &str,
+    ) -> Result<&Token, Box<ParseError>> {
         if self.check(&token_type) {
             return Ok(self.advance());
         }
-        Err(ParseError)
+        let opened_at = opened_at.clone();
+        let kind = match token_type {
+            TokenType::RightParen => ParseErrorKind::UnclosedParen { opened_at },
+            TokenType::RightBracket => ParseErrorKind::UnclosedBracket { opened_at },
+            _ => ParseErrorKind::UnclosedBrace { opened_at },
+        };
+        let error = self.error_at_current(kind, message);
+        Err(Box::new(ParseError {
+            expected: vec![token_type],
+            ..error
+        }))
+    }
+
+    // only builds the error; it's recorded wherever parsing recovers, or straight away by the
+    // callers that don't need to unwind at all
+    fn error(&self, token: &Token, kind: ParseErrorKind, message: &str) -> ParseError {
+        ParseError {
+            kind,
+            token: token.clone(),
+            message: message.to_string(),
+            expected: Vec::new(),
+        }
+    }
+
+    fn error_at_current(&self, kind: ParseErrorKind, message: &str) -> ParseError {
+        self.error(self.current_token(), kind, message)
+    }
+
+    // the token an error "at the current position" points at, the end of input included
+    fn current_token(&self) -> &Token {
+        self.tokens
+            .get(self.current)
+            .or(self.tokens.last())
+            .expect("the scanner always produces an Eof token")
+    }
+
+    /*
+    Panic mode: after an error, throw tokens away until something that looks like the start of a
+    statement, either just past a `;` or right before a keyword that begins a declaration or
+    statement. Whatever comes next is then parsed normally, so one mistake doesn't hide the
+    ones after it, although a few knock-on errors can still slip through.
+     */
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::SemiColon {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Enum
+                | TokenType::Export
+                | TokenType::Fun
+                | TokenType::Import
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try => return,
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
     fn match_tokens(&mut self, types: &[TokenType]) -> bool {
@@ -271,22 +1847,495 @@ impl Parser { // TODO: Return exit code on null error :) test 3 expected exit co
     }
 
     fn is_at_end(&self) -> bool {
-        ( self.tokens.len() == self.current|| self.peek().token_type == TokenType::Eof)
+        self.tokens.len() == self.current || self.peek().token_type == TokenType::Eof
     }
 
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
 
+    fn token_type_at(&self, position: usize) -> Option<&TokenType> {
+        self.tokens.get(position).map(|token| &token.token_type)
+    }
+
     fn previous(&self) -> &Token {
         &self.tokens[self.current - 1]
     }
 
-    pub(crate) fn parse(&mut self) -> Result<Expr, ParseError>{
+    // from `start` up to the end of the last token consumed
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
+    }
+
+    // fails with every error in the file, in the order they were found
+    pub(crate) fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration_or_recover() {
+                statements.push(statement);
+            }
+        }
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(statements)
+    }
+
+    pub(crate) fn parse_expression(&mut self) -> Result<Expr, Vec<ParseError>> {
         match self.expression() {
-            Ok(result) => Ok(result),
-            Err(ParseError) => Err(ParseError),
+            Ok(expression) if self.errors.is_empty() => Ok(expression),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(error) => {
+                self.errors.push(*error);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    // used wherever a list of declarations is parsed, so recovery also happens inside blocks
+    fn declaration_or_recover(&mut self) -> Option<Stmt> {
+        match self.declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(*error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        if self.match_tokens(&[TokenType::Class]) {
+            return self.class_declaration();
+        }
+
+        if self.match_tokens(&[TokenType::Enum]) {
+            return self.enum_declaration();
+        }
+
+        // `fun (` starts an anonymous function, which is left to the expression statement
+        if self.check(&TokenType::Fun)
+            && self.token_type_at(self.current + 1) != Some(&TokenType::LeftParen)
+        {
+            self.advance();
+            return Ok(Stmt::Function {
+                declaration: self.function("function")?,
+            });
+        }
+
+        if self.match_tokens(&[TokenType::Var]) {
+            return self.var_declaration();
+        }
+
+        if self.match_tokens(&[TokenType::Import]) {
+            return self.import_declaration();
+        }
+
+        if self.match_tokens(&[TokenType::Export]) {
+            return self.export_declaration();
+        }
+
+        self.statement()
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let keyword = self.previous().clone();
+        let path = self
+            .consume(TokenType::String, "Expect module path after 'import'.")?
+            .clone();
+
+        if !(self.check(&TokenType::Identifier) && self.peek().lexeme == "as") {
+            let error = self.error_at_current(
+                ParseErrorKind::ExpectedToken,
+                "Expect 'as' after module path.",
+            );
+            return Err(Box::new(ParseError {
+                expected: vec![TokenType::Identifier],
+                ..error
+            }));
+        }
+        self.advance();
+
+        let name = self
+            .consume(TokenType::Identifier, "Expect module name after 'as'.")?
+            .clone();
+        self.consume(TokenType::SemiColon, "Expect ';' after import.")?;
+        Ok(Stmt::Import {
+            keyword,
+            path,
+            name,
+        })
+    }
+
+    fn export_declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let declaration = if self.match_tokens(&[TokenType::Class]) {
+            self.class_declaration()?
+        } else if self.match_tokens(&[TokenType::Enum]) {
+            self.enum_declaration()?
+        } else if self.match_tokens(&[TokenType::Var]) {
+            self.var_declaration()?
+        } else if self.match_tokens(&[TokenType::Fun]) {
+            Stmt::Function {
+                declaration: self.function("function")?,
+            }
+        } else {
+            let error = self.error_at_current(
+                ParseErrorKind::ExpectedDeclaration,
+                "Expect declaration after 'export'.",
+            );
+            return Err(Box::new(ParseError {
+                expected: vec![TokenType::Class, TokenType::Enum, TokenType::Fun, TokenType::Var],
+                ..error
+            }));
+        };
+
+        Ok(Stmt::Export {
+            declaration: Box::new(declaration),
+        })
+    }
+
+    fn enum_declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect enum name.")?
+            .clone();
+        let opened_at = self.consume(TokenType::LeftBrace, "Expect '{' before enum body.")?.clone();
+
+        let mut variants = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let variant = self
+                .consume(TokenType::Identifier, "Expect variant name.")?
+                .clone();
+            let fields = if self.match_tokens(&[TokenType::LeftParen]) {
+                self.parameters()?
+            } else {
+                Vec::new()
+            };
+            variants.push((variant, fields));
+            if !self.match_tokens(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        self.consume_closing(TokenType::RightBrace, &opened_at, "Expect '}' after enum body.")?;
+        Ok(Stmt::Enum { name, variants })
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect class name.")?
+            .clone();
+
+        let mut superclass = None;
+        if self.match_tokens(&[TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Expr::Variable {
+                id: next_expr_id(),
+                name: self.previous().clone(),
+            });
+        }
+
+        let opened_at = self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?.clone();
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume_closing(TokenType::RightBrace, &opened_at, "Expect '}' after class body.")?;
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+        })
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, Box<ParseError>> {
+        let name = self
+            .consume(TokenType::Identifier, &format!("Expect {} name.", kind))?
+            .clone();
+        self.function_body(name, kind)
+    }
+
+    // everything after the name, shared by declarations, methods and `fun` expressions
+    fn function_body(&mut self, name: Token, kind: &str) -> Result<Rc<FunctionDecl>, Box<ParseError>> {
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+        let params = self.parameters()?;
+
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+        // a loop around the declaration doesn't make `break` valid inside the body
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block();
+        self.loop_depth = enclosing_loop_depth;
+        let body = body?;
+
+        Ok(Rc::new(FunctionDecl { name, params, body }))
+    }
+
+    // the opening paren has already been consumed, this eats the closing one
+    fn parameters(&mut self) -> Result<Vec<Token>, Box<ParseError>> {
+        let opened_at = self.previous().clone();
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name.")?
+                        .clone(),
+                );
+                if !self.match_tokens(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after parameters.")?;
+        Ok(params)
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+
+        let mut initializer = None;
+        if self.match_tokens(&[TokenType::Equal]) {
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        if self.match_tokens(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_control_statement();
+        }
+
+        if self.match_tokens(&[TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.match_tokens(&[TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.match_tokens(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+
+        if self.match_tokens(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_tokens(&[TokenType::Throw]) {
+            return self.throw_statement();
+        }
+
+        if self.match_tokens(&[TokenType::Try]) {
+            return self.try_statement();
+        }
+
+        if self.match_tokens(&[TokenType::While]) {
+            return self.while_statement();
+        }
+
+        if self.match_tokens(&[TokenType::LeftBrace]) {
+            return Ok(Stmt::Block {
+                statements: self.block()?,
+            });
+        }
+
+        self.expression_statement()
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let opened_at = self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?.clone();
+
+        let initializer = if self.match_tokens(&[TokenType::SemiColon]) {
+            None
+        } else if self.match_tokens(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(&TokenType::SemiColon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::SemiColon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(&TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after for clauses.")?;
+
+        let body = self.loop_body()?;
+
+        // desugar into a while loop: { initializer; while (condition) { body; increment; } }
+        // the increment stays on the loop itself so `continue` can't skip it
+        let mut body = Stmt::While {
+            condition: condition.unwrap_or(Expr::new_literal(Literal::Bool(true))),
+            body: Box::new(body),
+            increment,
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let opened_at = self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?.clone();
+        let condition = self.expression()?;
+        self.consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_tokens(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(&TokenType::SemiColon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::SemiColon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let keyword = self.previous().clone();
+        let value = self.expression()?;
+        self.consume(TokenType::SemiColon, "Expect ';' after thrown value.")?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let mut handler = None;
+        if self.match_tokens(&[TokenType::Catch]) {
+            let opened_at = self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?.clone();
+            let name = self
+                .consume(TokenType::Identifier, "Expect exception variable name.")?
+                .clone();
+            self.consume_closing(
+                TokenType::RightParen,
+                &opened_at,
+                "Expect ')' after exception variable.",
+            )?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            handler = Some((name, self.block()?));
+        }
+
+        let mut finally = None;
+        if self.match_tokens(&[TokenType::Finally]) {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block()?);
+        }
+
+        // a bare `try` block would just be a block
+        if handler.is_none() && finally.is_none() {
+            let error = self.error_at_current(
+                ParseErrorKind::MissingCatchOrFinally,
+                "Expect 'catch' or 'finally' after try block.",
+            );
+            return Err(Box::new(ParseError {
+                expected: vec![TokenType::Catch, TokenType::Finally],
+                ..error
+            }));
+        }
+
+        Ok(Stmt::Try {
+            body,
+            handler,
+            finally,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let opened_at = self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?.clone();
+        let condition = self.expression()?;
+        self.consume_closing(TokenType::RightParen, &opened_at, "Expect ')' after condition.")?;
+        let body = Box::new(self.loop_body()?);
+
+        Ok(Stmt::While {
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    fn loop_body(&mut self) -> Result<Stmt, Box<ParseError>> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn loop_control_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            // still parses fine, so there is nothing to recover from
+            let error = self.error(
+                &keyword,
+                ParseErrorKind::LoopControlOutsideLoop,
+                &format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+            );
+            self.errors.push(error);
+        }
+
+        self.consume(TokenType::SemiColon, &format!("Expect ';' after '{}'.", keyword.lexeme))?;
+        if keyword.token_type == TokenType::Break {
+            Ok(Stmt::Break)
+        } else {
+            Ok(Stmt::Continue)
+        }
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let expression = self.expression()?;
+        self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
+        Ok(Stmt::Print { expression })
+    }
+
+    // the opening brace has already been consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, Box<ParseError>> {
+        let opened_at = self.previous().clone();
+        let mut statements = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration_or_recover() {
+                statements.push(statement);
+            }
         }
+
+        self.consume_closing(TokenType::RightBrace, &opened_at, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
-    }
\ No newline at end of file
+    fn expression_statement(&mut self) -> Result<Stmt, Box<ParseError>> {
+        let expression = self.expression()?;
+        self.consume(TokenType::SemiColon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression { expression })
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+use crate::diagnostic::Diagnostic;
+use crate::evaluator::Interpreter;
+use crate::parser::{Expr, FunctionDecl, MatchArm, Pattern, Stmt};
+use crate::scanner::{Literal, Token};
+
+/*
+Static pass run between parsing and interpreting. Walks the tree once and tells the
+interpreter how many scopes separate each variable use from the scope that declared it, so
+closures keep seeing the binding that was in scope when they were written. Globals are left
+unresolved and looked up dynamically.
+ */
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // name -> has the initializer finished running
+    scopes: Vec<HashMap<String, bool>>,
+    // enum name -> its variant names, for the exhaustiveness check; a later declaration with
+    // the same name simply replaces an earlier one
+    enums: HashMap<String, Vec<String>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    pub had_error: bool,
+    // warnings as well as errors, in the order they were found
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: Vec::new(),
+            enums: HashMap::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            had_error: false,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve(statements);
+                self.end_scope();
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Function { declaration } => {
+                // defined eagerly so the function can refer to itself recursively
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable {
+                        name: superclass_name,
+                        ..
+                    } = superclass
+                    {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name, "A class can't inherit from itself.");
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expression(superclass);
+
+                    self.begin_scope();
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert("super".to_string(), true);
+                }
+
+                // methods close over a scope that holds `this`
+                self.begin_scope();
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert("this".to_string(), true);
+                for method in methods {
+                    let function_type = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, function_type);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.resolve_expression(expression);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment);
+                }
+            }
+            Stmt::Enum { name, variants } => {
+                self.declare(name);
+                self.define(name);
+
+                let mut names: Vec<String> = Vec::new();
+                for (variant, fields) in variants {
+                    if names.contains(&variant.lexeme) {
+                        self.error(variant, "Already a variant with this name in this enum.");
+                    }
+                    names.push(variant.lexeme.clone());
+
+                    let mut seen: Vec<&str> = Vec::new();
+                    for field in fields {
+                        if seen.contains(&field.lexeme.as_str()) {
+                            self.error(field, "Already a field with this name in this variant.");
+                        }
+                        seen.push(&field.lexeme);
+                    }
+                }
+                self.enums.insert(name.lexeme.clone(), names);
+            }
+            Stmt::Import { name, .. } => {
+                self.declare(name);
+                self.define(name);
+            }
+            Stmt::Export { declaration } => {
+                if !self.scopes.is_empty() {
+                    if let Some(name) = declaration.declared_name() {
+                        self.error(name, "Can only export from the top level of a module.");
+                    }
+                }
+                self.resolve_statement(declaration);
+            }
+            Stmt::Throw { value, .. } => self.resolve_expression(value),
+            Stmt::Try {
+                body,
+                handler,
+                finally,
+            } => {
+                self.begin_scope();
+                self.resolve(body);
+                self.end_scope();
+
+                // the caught value shares a scope with the catch block's own declarations
+                if let Some((name, handler_body)) = handler {
+                    self.begin_scope();
+                    self.declare(name);
+                    self.define(name);
+                    self.resolve(handler_body);
+                    self.end_scope();
+                }
+
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve(finally);
+                    self.end_scope();
+                }
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { id, name } => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        self.error(name, "Can't read local variable in its own initializer.");
+                    }
+                }
+                self.resolve_local(*id, name);
+            }
+            Expr::Assign { id, name, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_expression(then_branch);
+                self.resolve_expression(else_branch);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expression(object),
+            Expr::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::This { id, keyword } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::Super { id, keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(keyword, "Can't use 'super' outside of a class.")
+                    }
+                    ClassType::Class => {
+                        self.error(keyword, "Can't use 'super' in a class with no superclass.")
+                    }
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::List { elements } => {
+                for element in elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            }
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+                self.resolve_expression(value);
+            }
+            Expr::Grouping { expression } | Expr::Stringify { expression } => {
+                self.resolve_expression(expression)
+            }
+            Expr::Lambda { declaration } => {
+                self.resolve_function(declaration, FunctionType::Function)
+            }
+            Expr::Match {
+                keyword,
+                subject,
+                arms,
+            } => {
+                self.resolve_expression(subject);
+                for arm in arms {
+                    // classes named in the pattern are looked up outside the arm's own scope
+                    self.resolve_pattern_classes(&arm.pattern);
+
+                    self.begin_scope();
+                    let mut bindings = Vec::new();
+                    arm.pattern.bindings(&mut bindings);
+                    for name in bindings {
+                        self.declare(name);
+                        self.define(name);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.resolve_expression(guard);
+                    }
+                    self.resolve_expression(&arm.body);
+                    self.end_scope();
+                }
+                self.check_exhaustive(keyword, arms);
+            }
+            Expr::Literal { .. } => {}
+            Expr::Unary { right, .. } => self.resolve_expression(right),
+        }
+    }
+
+    fn resolve_pattern_classes(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Instance { class, fields } => {
+                self.resolve_expression(class);
+                for (_, field) in fields {
+                    self.resolve_pattern_classes(field);
+                }
+            }
+            Pattern::List { elements, .. } => {
+                for element in elements {
+                    self.resolve_pattern_classes(element);
+                }
+            }
+            Pattern::Map { entries } => {
+                for (_, entry) in entries {
+                    self.resolve_pattern_classes(entry);
+                }
+            }
+            Pattern::Variant {
+                enumeration,
+                fields,
+                ..
+            } => {
+                self.resolve_expression(enumeration);
+                for field in fields.iter().flatten() {
+                    self.resolve_pattern_classes(field);
+                }
+            }
+            Pattern::Wildcard | Pattern::Binding { .. } | Pattern::Literal { .. } => {}
+        }
+    }
+
+    /*
+    Only a closed set of values can be checked statically: a match whose unguarded arms are all
+    boolean literals must cover both, and one whose unguarded arms are all variants of a single
+    declared enum must cover every variant with fields that can't fail to match. Anything else is
+    open-ended and needs a catch-all arm to be exhaustive, which can't be demanded without
+    knowing what the subject will be. Guarded arms never count towards coverage. This is only a
+    warning, the runtime still errors on a miss.
+     */
+    fn check_exhaustive(&mut self, keyword: &Token, arms: &[MatchArm]) {
+        let unguarded: Vec<&Pattern> = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| &arm.pattern)
+            .collect();
+
+        // nothing to go on, or a catch-all that makes it exhaustive anyway
+        if unguarded.is_empty() || unguarded.iter().any(|pattern| is_irrefutable(pattern)) {
+            return;
+        }
+
+        let (subject, all, covered): (String, Vec<String>, Vec<String>) = if unguarded
+            .iter()
+            .all(|pattern| matches!(pattern, Pattern::Literal { value: Literal::Bool(_) }))
+        {
+            let covered = unguarded
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect();
+            (
+                "match".to_string(),
+                vec!["true".to_string(), "false".to_string()],
+                covered,
+            )
+        } else if let Some((enum_name, variants)) = self.single_enum(&unguarded) {
+            // a variant only counts as covered when its fields can't fail to match
+            let covered = unguarded
+                .iter()
+                .filter_map(|pattern| match pattern {
+                    Pattern::Variant {
+                        variant, fields, ..
+                    } if fields.iter().flatten().all(is_irrefutable) => {
+                        Some(variant.lexeme.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+            (format!("match over {}", enum_name), variants, covered)
+        } else {
+            return;
+        };
+
+        let missing: Vec<&str> = all
+            .iter()
+            .filter(|value| !covered.contains(value))
+            .map(|value| value.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.warning(
+                keyword,
+                &format!("Non-exhaustive {}, missing {}.", subject, missing.join(", ")),
+            );
+        }
+    }
+
+    // the enum every pattern is a variant of, if they all name the same one declared in this
+    // file. Variants reached through a module, like `m.Shape.Circle`, aren't known here, so
+    // those matches go unchecked
+    fn single_enum(&self, patterns: &[&Pattern]) -> Option<(String, Vec<String>)> {
+        let mut found: Option<&str> = None;
+        for pattern in patterns {
+            let Pattern::Variant {
+                enumeration: Expr::Variable { name, .. },
+                ..
+            } = pattern
+            else {
+                return None;
+            };
+            if found.is_some_and(|found| found != name.lexeme) {
+                return None;
+            }
+            found = Some(&name.lexeme);
+        }
+        let found = found?;
+        let variants = self.enums.get(found)?;
+        Some((found.to_string(), variants.clone()))
+    }
+
+    fn resolve_function(&mut self, function: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+        // not found in any local scope, assume it is global
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    // reported the same way as an error, but doesn't stop the program from running
+    fn warning(&mut self, token: &Token, message: &str) {
+        self.diagnostics.push(Diagnostic::warning(
+            format!("[line {}] Warning at '{}': {}", token.line, token.lexeme, message),
+            token.span,
+        ));
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.diagnostics.push(Diagnostic::error(
+            format!("[line {}] Error at '{}': {}", token.line, token.lexeme, message),
+            token.span,
+        ));
+        self.had_error = true;
+    }
+}
+
+// matches every value without looking at it
+fn is_irrefutable(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard | Pattern::Binding { .. })
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use std::fmt;
-use std::fmt::{format, Formatter, write};
-use std::process::exit;
-use std::sync::Once;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::OnceLock;
 use std::cmp::{PartialEq,Eq};
+use crate::diagnostic::Diagnostic;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TokenType {
@@ -11,38 +12,67 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Star,
+    StarStar,
+    Percent,
+    TildeSlash,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
     Dot,
     Comma,
+    Colon,
+    Question,
+    QuestionQuestion,
     Plus,
     Minus,
     EqualEqual,
     Equal,
+    Arrow,
     BangEqual,
     Bang,
     LessEqual,
     Less,
+    LessLess,
     GreaterEqual,
     Greater,
+    GreaterGreater,
     SemiColon,
     Slash,
     String,
+    // the part of an interpolated string before a `${`
+    Interpolation,
+    // the part after the last embedded expression, up to the closing quote
+    InterpolationEnd,
     Number,
     Identifier,
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
+    Enum,
+    Export,
     False,
+    Finally,
     For,
     Fun,
     If,
+    Import,
+    Match,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
     Eof,
@@ -50,18 +80,48 @@ pub enum TokenType {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
+    Int(i64),
     Number(f64),
     String(String),
     Bool(bool),
     Nil,
 }
 
+// A point in the source. Columns count characters rather than bytes, starting from 1, so they
+// match what an editor shows for non-ASCII text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+// The source range a token covers, from its first character up to just past its last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    // from the start of this span to the end of a later one
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            start: self.start,
+            end: end.end,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: Option<Literal>,
     pub line: usize,
+    pub span: Span,
+    // the file the token was scanned from, if the scanner was told which one it was reading
+    pub file: Option<Rc<Path>>,
 }
 // TODO: Need to change this from for TokenType to for Token so as to output the literal values
 // for the literals
@@ -73,8 +133,8 @@ impl fmt::Display for Token {
             "{} {} {}",
             self.token_type,
             match self.token_type{
-                TokenType::String => format!("\"{}\"", self.lexeme),
-                _ => format!("{}", self.lexeme)
+                TokenType::String | TokenType::Interpolation | TokenType::InterpolationEnd => format!("\"{}\"", self.lexeme),
+                _ => self.lexeme.to_string()
             },
             match &self.literal {
                 Some(Literal::Number(literal))  => format!("{:?}", literal),
@@ -88,6 +148,7 @@ impl fmt::Display for Token {
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Literal::Int(value) => write!(f, "{}", value),
             Literal::Number(value) => write!(f, "{}", value),
             Literal::String(value) => write!(f, "{}", value),
             Literal::Bool(value) => write!(f, "{}", value),
@@ -103,38 +164,65 @@ impl std::fmt::Display for TokenType {
             TokenType::RightParen => write!(f, "RIGHT_PAREN"),
             TokenType::LeftBrace => write!(f, "LEFT_BRACE"),
             TokenType::RightBrace => write!(f, "RIGHT_BRACE"),
+            TokenType::LeftBracket => write!(f, "LEFT_BRACKET"),
+            TokenType::RightBracket => write!(f, "RIGHT_BRACKET"),
             TokenType::Star => write!(f, "STAR"),
+            TokenType::StarStar => write!(f, "STAR_STAR"),
+            TokenType::Percent => write!(f, "PERCENT"),
+            TokenType::TildeSlash => write!(f, "TILDE_SLASH"),
+            TokenType::Ampersand => write!(f, "AMPERSAND"),
+            TokenType::Pipe => write!(f, "PIPE"),
+            TokenType::Caret => write!(f, "CARET"),
+            TokenType::Tilde => write!(f, "TILDE"),
             TokenType::Dot => write!(f, "DOT"),
             TokenType::Comma => write!(f, "COMMA"),
+            TokenType::Colon => write!(f, "COLON"),
+            TokenType::Question => write!(f, "QUESTION"),
+            TokenType::QuestionQuestion => write!(f, "QUESTION_QUESTION"),
             TokenType::Plus => write!(f, "PLUS"),
             TokenType::Minus => write!(f, "MINUS"),
             TokenType::EqualEqual => write!(f, "EQUAL_EQUAL"),
             TokenType::Equal => write!(f, "EQUAL"),
+            TokenType::Arrow => write!(f, "ARROW"),
             TokenType::BangEqual => write!(f, "BANG_EQUAL"),
             TokenType::Bang => write!(f, "BANG"),
             TokenType::LessEqual => write!(f, "LESS_EQUAL"),
             TokenType::Less => write!(f, "LESS"),
+            TokenType::LessLess => write!(f, "LESS_LESS"),
             TokenType::GreaterEqual => write!(f, "GREATER_EQUAL"),
             TokenType::Greater => write!(f, "GREATER"),
+            TokenType::GreaterGreater => write!(f, "GREATER_GREATER"),
             TokenType::SemiColon => write!(f, "SEMICOLON"),
             TokenType::Slash => write!(f, "SLASH"),
             TokenType::String => write!(f, "STRING"),
+            TokenType::Interpolation => write!(f, "INTERPOLATION"),
+            TokenType::InterpolationEnd => write!(f, "INTERPOLATION_END"),
             TokenType::Number => write!(f, "NUMBER"),
             TokenType::Identifier => write!(f, "IDENTIFIER"),
             TokenType::And => write!(f, "AND"),
+            TokenType::Break => write!(f, "BREAK"),
+            TokenType::Catch => write!(f, "CATCH"),
             TokenType::Class => write!(f, "CLASS"),
+            TokenType::Continue => write!(f, "CONTINUE"),
             TokenType::Else => write!(f, "ELSE"),
+            TokenType::Enum => write!(f, "ENUM"),
+            TokenType::Export => write!(f, "EXPORT"),
             TokenType::False => write!(f, "FALSE"),
+            TokenType::Finally => write!(f, "FINALLY"),
             TokenType::For => write!(f, "FOR"),
             TokenType::Fun => write!(f, "FUN"),
             TokenType::If => write!(f, "IF"),
+            TokenType::Import => write!(f, "IMPORT"),
+            TokenType::Match => write!(f, "MATCH"),
             TokenType::Nil => write!(f, "NIL"),
             TokenType::Or => write!(f, "OR"),
             TokenType::Print => write!(f, "PRINT"),
             TokenType::Return => write!(f, "RETURN"),
             TokenType::Super => write!(f, "SUPER"),
             TokenType::This => write!(f, "THIS"),
+            TokenType::Throw => write!(f, "THROW"),
             TokenType::True => write!(f, "TRUE"),
+            TokenType::Try => write!(f, "TRY"),
             TokenType::Var => write!(f, "VAR"),
             TokenType::While => write!(f, "WHILE"),
             TokenType::Eof => write!(f, "EOF"),
@@ -142,32 +230,88 @@ impl std::fmt::Display for TokenType {
     }
 }
 
-// Used Once and Option for thread-safe initialization of keywords
-static INIT: Once = Once::new();
-static mut KEYWORDS: Option<HashMap<&'static str, TokenType>> = None;
+// Used OnceLock for thread-safe initialization of keywords
+static KEYWORDS: OnceLock<HashMap<&'static str, TokenType>> = OnceLock::new();
 
 pub struct Scanner {
     pub tokens: Vec<Token>,
-    line: usize,
+    pub diagnostics: Vec<Diagnostic>,
+    file: Option<Rc<Path>>,
 }
 
 impl Scanner {
     pub fn new() -> Self {
         Self {
             tokens: Vec::new(),
-            line: 1,
+            diagnostics: Vec::new(),
+            file: None,
         }
     }
 
+    // tokens scanned from a file remember it, so imports and errors know where they came from
+    pub fn with_file(path: &Path) -> Self {
+        Self {
+            file: Some(Rc::from(path)),
+            ..Self::new()
+        }
+    }
+
+    fn error(&mut self, line: usize, span: Span, message: &str) {
+        self.diagnostics.push(Diagnostic::error(format!("[line {}] Error: {}", line, message), span));
+    }
+
     pub fn scan_and_tokenize(&mut self, input: &str) -> i32 {
         let mut return_code = 0;
         let mut line_nb = 1;
 
+        // one entry per `${` we are inside, counting the braces opened since then
+        let mut interpolations: Vec<usize> = Vec::new();
+
         let keywords = get_keywords();
-        let mut chars = input.chars().peekable();
-        while let Some(char_current) = chars.next() {
+        let mut chars = Cursor::new(input);
+        let mut start = chars.current_position();
+        let mut spanned = 0;
+        loop {
+            // whatever was pushed last time round covers everything consumed since then
+            let end = chars.current_position();
+            for token in &mut self.tokens[spanned..] {
+                token.span = Span { start, end };
+                token.file = self.file.clone();
+            }
+            spanned = self.tokens.len();
+            start = end;
+
+            let Some(char_current) = chars.next() else {
+                break;
+            };
             match char_current {
-                '(' | ')' | '{' | '}' | ',' | '.' | '-' | '+' | ';' | '*' => {
+                '}' if interpolations.last() == Some(&0) => {
+                    // end of an embedded expression, carry on with the rest of the string
+                    interpolations.pop();
+                    let empty = self
+                        .tokens
+                        .last()
+                        .is_some_and(|token| token.token_type == TokenType::Interpolation);
+                    if empty {
+                        self.error(
+                            line_nb,
+                            Span { start, end: chars.current_position() },
+                            "Empty string interpolation.",
+                        );
+                        return_code = 65;
+                    }
+                    if !self.scan_string_segment(&mut chars, start, true, &mut line_nb, &mut interpolations) {
+                        return_code = 65;
+                    }
+                }
+                '(' | ')' | '{' | '}' | '[' | ']' | ',' | ':' | '.' | '-' | '+' | ';' | '%' | '&' | '|' | '^' => {
+                    if let Some(depth) = interpolations.last_mut() {
+                        match char_current {
+                            '{' => *depth += 1,
+                            '}' => *depth -= 1,
+                            _ => {}
+                        }
+                    }
                     let char_str = &char_current.to_string();
                     if let Some(token_type) = keywords.get(char_str.as_str()) {
                         //println!("{} {} null", token_type, char_current);
@@ -176,11 +320,14 @@ impl Scanner {
                             lexeme: char_str.clone(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     } else {
-                        eprintln!(
-                            "[line {}] Error: Unexpected character: {}",
-                            line_nb, char_current
+                        self.error(
+                            line_nb,
+                            Span { start, end: chars.current_position() },
+                            &format!("Unexpected character: {}", char_current),
                         );
                         return_code = 65;
                     }
@@ -194,9 +341,22 @@ impl Scanner {
                             lexeme: "==".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                         continue;
                     }
+                    Some('>') => {
+                        chars.next();
+                        self.tokens.push(Token {
+                            token_type: TokenType::Arrow,
+                            lexeme: "=>".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
                     _ => {
                        // println!("EQUAL = null");
                         self.tokens.push(Token {
@@ -204,6 +364,76 @@ impl Scanner {
                             lexeme: "=".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
+                },
+                '*' => match chars.peek() {
+                    Some('*') => {
+                        chars.next();
+                        self.tokens.push(Token {
+                            token_type: TokenType::StarStar,
+                            lexeme: "**".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
+                    _ => {
+                        self.tokens.push(Token {
+                            token_type: TokenType::Star,
+                            lexeme: "*".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
+                },
+                // floor division, `//` is already taken by comments
+                '~' if chars.peek() == Some(&'/') => {
+                    chars.next();
+                    self.tokens.push(Token {
+                        token_type: TokenType::TildeSlash,
+                        lexeme: "~/".to_string(),
+                        literal: None,
+                        line: line_nb,
+                        span: Span::default(),
+                        file: None,
+                    });
+                }
+                '~' => {
+                    self.tokens.push(Token {
+                        token_type: TokenType::Tilde,
+                        lexeme: "~".to_string(),
+                        literal: None,
+                        line: line_nb,
+                        span: Span::default(),
+                        file: None,
+                    });
+                }
+                '?' => match chars.peek() {
+                    Some('?') => {
+                        chars.next();
+                        self.tokens.push(Token {
+                            token_type: TokenType::QuestionQuestion,
+                            lexeme: "??".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
+                    _ => {
+                        self.tokens.push(Token {
+                            token_type: TokenType::Question,
+                            lexeme: "?".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 },
@@ -216,6 +446,8 @@ impl Scanner {
                             lexeme: "!=".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                         continue;
                     }
@@ -226,6 +458,8 @@ impl Scanner {
                             lexeme: "!".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 },
@@ -238,9 +472,22 @@ impl Scanner {
                             lexeme: "<=".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                         continue;
                     }
+                    Some('<') => {
+                        chars.next();
+                        self.tokens.push(Token {
+                            token_type: TokenType::LessLess,
+                            lexeme: "<<".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
                     _ => {
                        // println!("LESS < null");
                         self.tokens.push(Token {
@@ -248,6 +495,8 @@ impl Scanner {
                             lexeme: "<".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 },
@@ -260,9 +509,22 @@ impl Scanner {
                             lexeme: ">=".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                         continue;
                     }
+                    Some('>') => {
+                        chars.next();
+                        self.tokens.push(Token {
+                            token_type: TokenType::GreaterGreater,
+                            lexeme: ">>".to_string(),
+                            literal: None,
+                            line: line_nb,
+                            span: Span::default(),
+                            file: None,
+                        });
+                    }
                     _ => {
                         //println!("GREATER > null");
                         self.tokens.push(Token {
@@ -270,6 +532,8 @@ impl Scanner {
                             lexeme: ">".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 },
@@ -290,51 +554,29 @@ impl Scanner {
                             lexeme: "/".to_string(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 },
                 '"' => {
-                    let mut string_tok = String::from("");
-                    let mut ending = false;
-                    while let Some(next_char) = chars.next() {
-                        match next_char {
-                            '\n' => {
-                                eprintln!("[line {}] Error: Unterminated string.", line_nb);
-                                line_nb += 1;
-                                break;
-                            }
-                            '"' => {
-                                //println!("STRING \"{}\" {}", string_tok, string_tok);
-                                ending = true;
-                                self.tokens.push(Token {
-                                    token_type: TokenType::String,
-                                    lexeme: string_tok.clone(),
-                                    literal: Some(Literal::String(string_tok.clone())),
-                                    line: line_nb,
-                                });
-                                break;
-                            }
-                            a => string_tok.push(a),
-                        }
-                    }
-                    if !chars.peek().is_some() && !ending {
-                        eprintln!("[line {}] Error: Unterminated string.", line_nb);
+                    if !self.scan_string_segment(&mut chars, start, false, &mut line_nb, &mut interpolations) {
                         return_code = 65;
                     }
                 }
                 ' ' | '\t' | '\r' => continue,
                 '\n' => line_nb += 1,
-                a if a.is_digit(10) => {
+                a if a.is_ascii_digit() => {
                     let mut out_number = a.to_string();
                     let mut point = false;
                     while let Some(&next_char) = chars.peek() {
-                        if next_char.is_digit(10) {
+                        if next_char.is_ascii_digit() {
                             out_number.push(next_char);
                             chars.next();
                         } else if next_char == '.' && !point {
                             let mut chars_clone = chars.clone();
                             chars_clone.next();
-                            if let Some(&next_next_char) = chars_clone.peek() {
+                            if chars_clone.peek().is_some() {
                                 point = true;
                                 out_number.push(next_char);
                                 chars.next();
@@ -349,13 +591,34 @@ impl Scanner {
                         out_number.pop();
                        // println!("DOT . null"); // this is a quick fix and needs refactoring
                     }
-                    let out_number_float = out_number.parse::<f64>().unwrap();
+                    // no decimal point makes it an int, which has to fit an i64. The one
+                    // exception is 9223372036854775808 after a minus, the parser folds the two
+                    // into i64::MIN; until then it is only representable as a float
+                    let literal = if point {
+                        Literal::Number(out_number.parse::<f64>().unwrap())
+                    } else if let Ok(value) = out_number.parse::<i64>() {
+                        Literal::Int(value)
+                    } else if out_number == i64::MIN.unsigned_abs().to_string()
+                        && self.tokens.last().is_some_and(|token| token.token_type == TokenType::Minus)
+                    {
+                        Literal::Number(out_number.parse::<f64>().unwrap())
+                    } else {
+                        self.error(
+                            line_nb,
+                            Span { start, end: chars.current_position() },
+                            "Integer literal too large.",
+                        );
+                        return_code = 65;
+                        continue;
+                    };
                     //println!("NUMBER {} {:?}", out_number, out_number_float);
                     self.tokens.push(Token {
                         token_type: TokenType::Number,
                         lexeme: out_number.clone(),
-                        literal: Some(Literal::Number(out_number_float)),
+                        literal: Some(literal),
                         line: line_nb,
+                        span: Span::default(),
+                        file: None,
                     });
                 }
                 a if a.is_alphanumeric() || a == '_' => {
@@ -376,6 +639,8 @@ impl Scanner {
                             lexeme: token_out.clone(),
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     } else {
                         //println!("IDENTIFIER {} null", token_out);
@@ -384,69 +649,280 @@ impl Scanner {
                             lexeme: token_out,
                             literal: None,
                             line: line_nb,
+                            span: Span::default(),
+                            file: None,
                         });
                     }
                 }
                 a => {
-                    eprintln!("[line {}] Error: Unexpected character: {}", line_nb, a);
+                    self.error(
+                        line_nb,
+                        Span { start, end: chars.current_position() },
+                        &format!("Unexpected character: {}", a),
+                    );
                     return_code = 65;
                 }
             }
         }
-        //println!("EOF  null");
-        return return_code;
+        if !interpolations.is_empty() {
+            let end = chars.current_position();
+            self.error(line_nb, Span { start: end, end }, "Unterminated string interpolation.");
+            return_code = 65;
+        }
+        // gives the parser a token to point at when it runs out of input
+        self.tokens.push(Token {
+            token_type: TokenType::Eof,
+            lexeme: String::new(),
+            literal: None,
+            line: line_nb,
+            span: Span { start, end: start },
+            file: self.file.clone(),
+        });
+        return_code
+    }
+
+    // Scans a string literal from just after its opening quote (or the `}` closing an
+    // interpolated expression) up to the closing quote or the next `${`. Returns false if
+    // anything was malformed; the error has already been reported.
+    fn scan_string_segment(
+        &mut self,
+        chars: &mut Cursor,
+        start: Position,
+        closes_interpolation: bool,
+        line_nb: &mut usize,
+        interpolations: &mut Vec<usize>,
+    ) -> bool {
+        let mut lexeme = String::new();
+        let mut value = String::new();
+        let mut valid = true;
+
+        loop {
+            let at = chars.current_position();
+            let Some(next_char) = chars.next() else {
+                self.error(*line_nb, Span { start, end: at }, "Unterminated string.");
+                return false;
+            };
+            match next_char {
+                '\n' => {
+                    self.error(*line_nb, Span { start, end: at }, "Unterminated string.");
+                    *line_nb += 1;
+                    return false;
+                }
+                '"' => {
+                    // marked apart from an ordinary string, so the parser can't take one for the other
+                    let token_type = if closes_interpolation {
+                        TokenType::InterpolationEnd
+                    } else {
+                        TokenType::String
+                    };
+                    self.tokens.push(Token {
+                        token_type,
+                        lexeme,
+                        literal: Some(Literal::String(value)),
+                        line: *line_nb,
+                        span: Span::default(),
+                        file: None,
+                    });
+                    return valid;
+                }
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    self.tokens.push(Token {
+                        token_type: TokenType::Interpolation,
+                        lexeme,
+                        literal: Some(Literal::String(value)),
+                        line: *line_nb,
+                        span: Span::default(),
+                        file: None,
+                    });
+                    interpolations.push(0);
+                    return valid;
+                }
+                '\\' => {
+                    lexeme.push('\\');
+                    match chars.peek() {
+                        None | Some('\n') => continue, // reported as unterminated above
+                        Some(_) => {}
+                    }
+                    let escape = chars.next().unwrap();
+                    lexeme.push(escape);
+                    match escape {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        '0' => value.push('\0'),
+                        '"' => value.push('"'),
+                        '\\' => value.push('\\'),
+                        '$' => value.push('$'),
+                        'u' => match scan_unicode_escape(chars, &mut lexeme) {
+                            Some(decoded) => value.push(decoded),
+                            None => {
+                                let escape = Span { start: at, end: chars.current_position() };
+                                self.error(*line_nb, escape, "Invalid unicode escape sequence.");
+                                valid = false;
+                            }
+                        },
+                        other => {
+                            let escape = Span { start: at, end: chars.current_position() };
+                            self.error(
+                                *line_nb,
+                                escape,
+                                &format!("Invalid escape sequence: \\{}", other),
+                            );
+                            valid = false;
+                        }
+                    }
+                }
+                other => {
+                    lexeme.push(other);
+                    value.push(other);
+                }
+            }
+        }
     }
 }
 
-fn initialise_keywords() { // prety skitz way of doing it could be simplified for sure -- for another day
+// Walks the source one character at a time like `Peekable<Chars>`, keeping track of where the
+// next character starts so tokens can record their spans
+#[derive(Clone)]
+struct Cursor<'a> {
+    source: &'a str,
+    position: Position,
+    next: Option<char>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            position: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            next: source.chars().next(),
+        }
+    }
+
+    fn peek(&self) -> Option<&char> {
+        self.next.as_ref()
+    }
+
+    fn current_position(&self) -> Position {
+        self.position
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let current = self.next?;
+        self.position.offset += current.len_utf8();
+        if current == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+        self.next = self.source[self.position.offset..].chars().next();
+        Some(current)
+    }
+}
+
+// Reads the `{XXXX}` part of a `\u{XXXX}` escape: one to six hex digits naming a valid
+// code point
+fn scan_unicode_escape(chars: &mut Cursor, lexeme: &mut String) -> Option<char> {
+    if chars.peek() != Some(&'{') {
+        return None;
+    }
+    lexeme.push(chars.next()?);
+
+    let mut digits = String::new();
+    while let Some(&next_char) = chars.peek() {
+        if !next_char.is_ascii_hexdigit() {
+            break;
+        }
+        digits.push(next_char);
+        lexeme.push(next_char);
+        chars.next();
+    }
+
+    if chars.peek() != Some(&'}') || digits.is_empty() || digits.len() > 6 {
+        return None;
+    }
+    lexeme.push(chars.next()?);
+
+    u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+}
+
+fn initialise_keywords() -> HashMap<&'static str, TokenType> { // prety skitz way of doing it could be simplified for sure -- for another day
     let mut m = HashMap::new();
 
     m.insert("(", TokenType::LeftParen);
     m.insert(")", TokenType::RightParen);
     m.insert("{", TokenType::LeftBrace);
     m.insert("}", TokenType::RightBrace);
+    m.insert("[", TokenType::LeftBracket);
+    m.insert("]", TokenType::RightBracket);
     m.insert("*", TokenType::Star);
+    m.insert("**", TokenType::StarStar);
+    m.insert("%", TokenType::Percent);
+    m.insert("~/", TokenType::TildeSlash);
+    m.insert("&", TokenType::Ampersand);
+    m.insert("|", TokenType::Pipe);
+    m.insert("^", TokenType::Caret);
+    m.insert("~", TokenType::Tilde);
     m.insert(".", TokenType::Dot);
     m.insert(",", TokenType::Comma);
+    m.insert(":", TokenType::Colon);
+    m.insert("?", TokenType::Question);
+    m.insert("??", TokenType::QuestionQuestion);
     m.insert("+", TokenType::Plus);
     m.insert("-", TokenType::Minus);
     m.insert("==", TokenType::EqualEqual);
     m.insert("=", TokenType::Equal);
+    m.insert("=>", TokenType::Arrow);
     m.insert("!=", TokenType::BangEqual);
     m.insert("!", TokenType::Bang);
     m.insert("<=", TokenType::LessEqual);
     m.insert("<", TokenType::Less);
+    m.insert("<<", TokenType::LessLess);
     m.insert(">=", TokenType::GreaterEqual);
     m.insert(">", TokenType::Greater);
+    m.insert(">>", TokenType::GreaterGreater);
     m.insert(";", TokenType::SemiColon);
     m.insert("/", TokenType::Slash);
     m.insert("and", TokenType::And);
+    m.insert("break", TokenType::Break);
+    m.insert("catch", TokenType::Catch);
     m.insert("class", TokenType::Class);
+    m.insert("continue", TokenType::Continue);
     m.insert("else", TokenType::Else);
+    m.insert("enum", TokenType::Enum);
+    m.insert("export", TokenType::Export);
     m.insert("false", TokenType::False);
+    m.insert("finally", TokenType::Finally);
     m.insert("for", TokenType::For);
     m.insert("fun", TokenType::Fun);
     m.insert("if", TokenType::If);
+    m.insert("import", TokenType::Import);
+    m.insert("match", TokenType::Match);
     m.insert("nil", TokenType::Nil);
     m.insert("or", TokenType::Or);
     m.insert("print", TokenType::Print);
     m.insert("return", TokenType::Return);
     m.insert("super", TokenType::Super);
     m.insert("this", TokenType::This);
+    m.insert("throw", TokenType::Throw);
     m.insert("true", TokenType::True);
+    m.insert("try", TokenType::Try);
     m.insert("var", TokenType::Var);
     m.insert("while", TokenType::While);
     m.insert("EOF", TokenType::Eof);
-    unsafe {
-        KEYWORDS = Some(m);
-    }
+    m
 }
 
 fn get_keywords() -> &'static HashMap<&'static str, TokenType> {
-    unsafe {
-        INIT.call_once(|| {
-            initialise_keywords();
-        });
-        KEYWORDS.as_ref().unwrap()
-    }
+    KEYWORDS.get_or_init(initialise_keywords)
 }